@@ -1,43 +1,42 @@
 extern crate dhcp4r;
 extern crate time;
 
-use dhcp4r::{options, packet, server};
+use dhcp4r::{options, packet};
 use std::net::{Ipv4Addr, UdpSocket};
 
 fn main() {
-    server::Server::serve(
-        UdpSocket::bind("0.0.0.0:67").unwrap(),
-        Ipv4Addr::new(0, 0, 0, 0),
-        MyServer {},
-    );
-}
-
-struct MyServer {}
-
-impl server::Handler for MyServer {
-    fn handle_request(&mut self, _: &server::Server, in_packet: packet::Packet) {
-        match in_packet.message_type() {
-            Ok(options::MessageType::Request) => {
-                let req_ip = match in_packet.option(options::REQUESTED_IP_ADDRESS) {
-                    Some(options::DhcpOption::RequestedIpAddress(x)) => x.clone(),
-                    _ => in_packet.ciaddr,
-                };
-                println!(
-                    "{}\t{}\t{}\tOnline",
-                    time::OffsetDateTime::now_local().format("%Y-%m-%dT%H:%M:%S"),
-                    chaddr(&in_packet.chaddr),
-                    Ipv4Addr::from(req_ip)
-                );
+    // -v dumps every packet seen, in full
+    let verbose = std::env::args().any(|a| a == "-v");
+    let socket = UdpSocket::bind("0.0.0.0:67").unwrap();
+    let mut buf = [0; 1500];
+    loop {
+        let (len, src) = socket.recv_from(&mut buf).unwrap();
+        // Borrowed view avoids allocating for each packet seen
+        if let Ok(p) = packet::PacketRef::new(&buf[..len]) {
+            if verbose {
+                match p.to_packet() {
+                    Ok(p) => println!("{} from {}\n{}", p, src, p.dump()),
+                    Err(e) => println!("Undecodable packet from {}: {}", src, e),
+                }
             }
-            _ => {}
+            handle_request(p);
         }
     }
 }
 
-/// Formats byte array machine address into hex pairs separated by colons.
-/// Array must be at least one byte long.
-fn chaddr(a: &[u8]) -> String {
-    a[1..].iter().fold(format!("{:02x}", a[0]), |acc, &b| {
-        format!("{}:{:02x}", acc, &b)
-    })
+fn handle_request(in_packet: packet::PacketRef) {
+    if let Some(options::MessageType::Request) = in_packet.message_type() {
+        let req_ip = match in_packet.raw_option(options::REQUESTED_IP_ADDRESS) {
+            Some(&[a, b, c, d]) => Ipv4Addr::new(a, b, c, d),
+            _ => in_packet.ciaddr(),
+        };
+        println!(
+            "{}\t{}\t{}\tOnline",
+            time::OffsetDateTime::try_now_local()
+                .unwrap_or_else(|_| time::OffsetDateTime::now_utc())
+                .format("%Y-%m-%dT%H:%M:%S"),
+            in_packet.chaddr(),
+            req_ip
+        );
+    }
 }
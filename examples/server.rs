@@ -1,4 +1,4 @@
-#[macro_use(u32_bytes, bytes_u32)]
+#[macro_use(bytes_u32)]
 extern crate dhcp4r;
 
 use std::collections::HashMap;
@@ -38,7 +38,7 @@ fn main() {
 }
 
 struct MyServer {
-    leases: HashMap<Ipv4Addr, ([u8; 6], Instant)>,
+    leases: HashMap<Ipv4Addr, (packet::HardwareAddress, Instant)>,
     last_lease: u32,
     lease_duration: Duration,
 }
@@ -67,13 +67,13 @@ impl server::Handler for MyServer {
                     self.last_lease = (self.last_lease + 1) % LEASE_NUM;
                     if self.available(
                         &in_packet.chaddr,
-                        &((IP_START_NUM + &self.last_lease).into()),
+                        &((IP_START_NUM + self.last_lease).into()),
                     ) {
                         reply(
                             server,
                             options::MessageType::Offer,
                             in_packet,
-                            &((IP_START_NUM + &self.last_lease).into()),
+                            &((IP_START_NUM + self.last_lease).into()),
                         );
                         break;
                     }
@@ -117,23 +117,22 @@ impl server::Handler for MyServer {
 }
 
 impl MyServer {
-    fn available(&self, chaddr: &[u8; 6], addr: &Ipv4Addr) -> bool {
+    fn available(&self, chaddr: &packet::HardwareAddress, addr: &Ipv4Addr) -> bool {
         let pos: u32 = (*addr).into();
-        pos >= IP_START_NUM
-            && pos < IP_START_NUM + LEASE_NUM
+        (IP_START_NUM..IP_START_NUM + LEASE_NUM).contains(&pos)
             && match self.leases.get(addr) {
                 Some(x) => x.0 == *chaddr || Instant::now().gt(&x.1),
                 None => true,
             }
     }
 
-    fn current_lease(&self, chaddr: &[u8; 6]) -> Option<Ipv4Addr> {
+    fn current_lease(&self, chaddr: &packet::HardwareAddress) -> Option<Ipv4Addr> {
         for (i, v) in &self.leases {
             if &v.0 == chaddr {
                 return Some(*i);
             }
         }
-        return None;
+        None
     }
 }
 
@@ -0,0 +1,173 @@
+//! Async version of the server module, built on tokio.
+
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
+
+use crate::options::{DhcpOption, MessageType};
+use crate::packet::*;
+use crate::server::{
+    bootp_reply_packet, encode_reply, for_server, is_bootp_request, reply_packet, StaticBinding,
+};
+
+pub struct Server {
+    socket: UdpSocket,
+    src: SocketAddr,
+    max_message_size: usize,
+    server_ip: Ipv4Addr,
+}
+
+/// Handles requests for Server::serve, which waits for each request to be handled before
+/// receiving the next. Implementations may use async fn.
+pub trait Handler {
+    fn handle_request(
+        &mut self,
+        server: &Server,
+        in_packet: Packet,
+    ) -> impl Future<Output = ()> + Send;
+
+    /// Called for BOOTP requests (RFC 951), which have no DHCP_MESSAGE_TYPE.
+    /// Answer them with Server::reply_bootp. By default they are passed to handle_request.
+    fn handle_bootp_request(
+        &mut self,
+        server: &Server,
+        in_packet: Packet,
+    ) -> impl Future<Output = ()> + Send {
+        self.handle_request(server, in_packet)
+    }
+}
+
+impl Server {
+    /// Serves requests until shutdown is cancelled, returning Ok once the request being handled,
+    /// if any, is complete.
+    pub async fn serve<H: Handler>(
+        udp_soc: UdpSocket,
+        server_ip: Ipv4Addr,
+        mut handler: H,
+        shutdown: CancellationToken,
+    ) -> io::Result<()> {
+        let mut in_buf: [u8; 1500] = [0; 1500];
+        let mut s = Server {
+            socket: udp_soc,
+            server_ip,
+            src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
+            max_message_size: MIN_MESSAGE_SIZE,
+        };
+        loop {
+            let (l, src) = tokio::select! {
+                _ = shutdown.cancelled() => return Ok(()),
+                r = s.socket.recv_from(&mut in_buf) => r?,
+            };
+            // Leniently, to serve embedded clients that omit END or fill sname/file
+            if let Ok((_, p)) = Packet::decode(&in_buf[..l], true) {
+                s.src = src;
+                s.max_message_size = p.max_message_size();
+                if is_bootp_request(&p) {
+                    handler.handle_bootp_request(&s, p).await;
+                } else {
+                    handler.handle_request(&s, p).await;
+                }
+            }
+        }
+    }
+
+    /// Constructs and sends a reply packet back to the client, see server::Server::reply.
+    pub async fn reply(
+        &self,
+        msg_type: MessageType,
+        additional_options: Vec<DhcpOption>,
+        offer_ip: Ipv4Addr,
+        req_packet: Packet,
+    ) -> io::Result<usize> {
+        let p = reply_packet(
+            self.server_ip,
+            msg_type,
+            additional_options,
+            offer_ip,
+            &req_packet,
+        );
+        self.send(p).await
+    }
+
+    /// Constructs and sends a reply to a BOOTP client from its static binding, see
+    /// server::Server::reply_bootp.
+    pub async fn reply_bootp(
+        &self,
+        binding: &StaticBinding,
+        req_packet: Packet,
+    ) -> io::Result<usize> {
+        self.send(bootp_reply_packet(binding, &req_packet)?).await
+    }
+
+    /// Checks the packet see if it was intended for this DHCP server (as opposed to some other also on the network).
+    pub fn for_this_server(&self, packet: &Packet) -> bool {
+        for_server(packet, self.server_ip)
+    }
+
+    /// Encodes and sends a DHCP packet back to the client, see server::Server::send.
+    pub async fn send(&self, p: Packet) -> io::Result<usize> {
+        let (out, addr) = encode_reply(&p, self.src, self.max_message_size)?;
+        self.socket.send_to(&out, addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options;
+
+    struct Offerer;
+
+    impl Handler for Offerer {
+        async fn handle_request(&mut self, server: &Server, in_packet: Packet) {
+            server
+                .reply(
+                    MessageType::Offer,
+                    vec![DhcpOption::IpAddressLeaseTime(3600)],
+                    Ipv4Addr::new(192, 168, 0, 10),
+                    in_packet,
+                )
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_until_shutdown() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let shutdown = CancellationToken::new();
+        let server_ip = Ipv4Addr::new(192, 168, 0, 1);
+        let server = tokio::spawn(Server::serve(socket, server_ip, Offerer, shutdown.clone()));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let discover = PacketBuilder::request(HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]))
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+        let mut out = discover.encode_to_vec(MIN_MESSAGE_SIZE).unwrap();
+        // Non-UTF-8 sname, and END replaced by PAD
+        out[44] = 0xff;
+        assert_eq!(out[243], 255);
+        out[243] = 0;
+        assert!(Packet::from(&out).is_err());
+        client.send_to(&out, addr).await.unwrap();
+
+        let mut buf = [0; 1500];
+        let (len, _) = client.recv_from(&mut buf).await.unwrap();
+        let offer = Packet::from(&buf[..len]).unwrap();
+        assert_eq!(offer.xid, discover.xid);
+        assert_eq!(offer.message_type(), Ok(MessageType::Offer));
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 0, 10));
+        assert_eq!(
+            offer.option(options::SERVER_IDENTIFIER),
+            Some(&DhcpOption::ServerIdentifier(server_ip))
+        );
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -2,9 +2,15 @@
 extern crate enum_primitive_derive;
 extern crate num_traits;
 
+#[cfg(feature = "tokio")]
+pub mod async_server;
 pub mod options;
 pub mod packet;
+pub mod pcap;
+#[cfg(feature = "serde")]
+mod serde_impls;
 pub mod server;
+pub mod vendor;
 
 /// Converts a u32 to 4 bytes (Big endian)
 #[macro_export]
@@ -1,30 +1,852 @@
+use crate::packet::{has_builtin_decoder, DecodeErrorKind, EncodeError};
+pub use ipnet::Ipv4Net;
 use num_traits::FromPrimitive;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
 use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
 
+/// With the serde feature, serialized with the title of its code as name, if known.
 #[derive(PartialEq, Clone, Debug)]
 pub struct RawDhcpOption {
     pub code: u8,
     pub data: Vec<u8>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DhcpOption {
-    DhcpMessageType(MessageType),
-    ServerIdentifier(Ipv4Addr),
-    ParameterRequestList(Vec<u8>),
-    RequestedIpAddress(Ipv4Addr),
-    HostName(String),
+    SubnetMask(Ipv4Addr),
+    TimeOffset(i32),
     Router(Vec<Ipv4Addr>),
+    TimeServer(Vec<Ipv4Addr>),
+    NameServer(Vec<Ipv4Addr>),
     DomainNameServer(Vec<Ipv4Addr>),
+    LogServer(Vec<Ipv4Addr>),
+    CookieServer(Vec<Ipv4Addr>),
+    LprServer(Vec<Ipv4Addr>),
+    ImpressServer(Vec<Ipv4Addr>),
+    ResourceLocationServer(Vec<Ipv4Addr>),
+    HostName(String),
+    BootFileSize(u16),
+    MeritDumpFile(String),
+    DomainName(String),
+    SwapServer(Ipv4Addr),
+    RootPath(String),
+    ExtensionsPath(String),
+
+    // IP layer parameters per host
+    IpForwardingEnableDisable(bool),
+    NonLocalSourceRoutingEnableDisable(bool),
+    PolicyFilter(Vec<(Ipv4Addr, Ipv4Addr)>),
+    MaximumDatagramReassemblySize(u16),
+    DefaultIpTimeToLive(u8),
+    PathMtuAgingTimeout(u32),
+    PathMtuPlateauTable(Vec<u16>),
+
+    // IP layer parameters per interface
+    InterfaceMtu(u16),
+    AllSubnetsAreLocal(bool),
+    BroadcastAddress(Ipv4Addr),
+    PerformMaskDiscovery(bool),
+    MaskSupplier(bool),
+    PerformRouterDiscovery(bool),
+    RouterSolicitationAddress(Ipv4Addr),
+    StaticRoute(Vec<(Ipv4Addr, Ipv4Addr)>),
+
+    // Link layer parameters per interface
+    TrailerEncapsulation(bool),
+    ArpCacheTimeout(u32),
+    EthernetEncapsulation(bool),
+
+    // TCP parameters
+    TcpDefaultTtl(u8),
+    TcpKeepaliveInterval(u32),
+    TcpKeepaliveGarbage(bool),
+
+    // Application and service parameters
+    NetworkInformationServiceDomain(String),
+    NetworkInformationServers(Vec<Ipv4Addr>),
+    NetworkTimeProtocolServers(Vec<Ipv4Addr>),
+    /// Vendor specific data as received, since its format depends on VENDOR_CLASS_IDENTIFIER.
+    /// See the vendor module for interpreting it.
+    VendorSpecificInformation(Vec<u8>),
+    NetbiosOverTcpipNameServer(Vec<Ipv4Addr>),
+    NetbiosOverTcpipDatagramDistributionServer(Vec<Ipv4Addr>),
+    NetbiosOverTcpipNodeType(u8),
+    NetbiosOverTcpipScope(String),
+    XwindowSystemFontServer(Vec<Ipv4Addr>),
+    XwindowSystemDisplayManager(Vec<Ipv4Addr>),
+    NetworkInformationServiceplusDomain(String),
+    NetworkInformationServiceplusServers(Vec<Ipv4Addr>),
+    MobileIpHomeAgent(Vec<Ipv4Addr>),
+    SimpleMailTransportProtocol(Vec<Ipv4Addr>),
+    PostOfficeProtocolServer(Vec<Ipv4Addr>),
+    NetworkNewsTransportProtocol(Vec<Ipv4Addr>),
+    DefaultWorldWideWebServer(Vec<Ipv4Addr>),
+    DefaultFingerServer(Vec<Ipv4Addr>),
+    DefaultInternetRelayChatServer(Vec<Ipv4Addr>),
+    StreettalkServer(Vec<Ipv4Addr>),
+    StreettalkDirectoryAssistance(Vec<Ipv4Addr>),
+
+    // DHCP extensions
+    RequestedIpAddress(Ipv4Addr),
     IpAddressLeaseTime(u32),
-    SubnetMask(Ipv4Addr),
+    DhcpMessageType(MessageType),
+    ServerIdentifier(Ipv4Addr),
+    /// Serialized with each code's title in place of its number, if known.
+    ParameterRequestList(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::option_codes"))] Vec<u8>,
+    ),
     Message(String),
+    MaximumDhcpMessageSize(u16),
+    RenewalTimeValue(u32),
+    RebindingTimeValue(u32),
+    VendorClassIdentifier(Vec<u8>),
+    /// htype is CLIENT_ID_RFC4361 when id holds an IAID and DUID, see iaid_duid().
+    ClientIdentifier {
+        htype: u8,
+        id: Vec<u8>,
+    },
+    TftpServerName(String),
+    BootfileName(String),
+    TzPosixString(String),
+    TzDatabaseString(String),
+    /// RFC 3397 search list. Names are compressed on encoding, and labels longer than 63
+    /// bytes are truncated.
+    DomainSearch(Vec<String>),
+    /// RFC 4578 client system architectures, in order of preference.
+    ClientArchitecture(Vec<Architecture>),
+    ClasslessStaticRoute(Vec<(Ipv4Net, Ipv4Addr)>),
+    /// Microsoft's pre-standard code for ClasslessStaticRoute, with the same encoding.
+    MsClasslessStaticRoute(Vec<(Ipv4Net, Ipv4Addr)>),
+    /// RFC 3004 user classes. Data that is not a valid list, such as iPXE's bare "iPXE",
+    /// is decoded as a single class, and is re-encoded as a valid list. Classes longer than 255
+    /// bytes cannot be encoded.
+    UserClass(Vec<Vec<u8>>),
+    /// RFC 4702 client FQDN. name is in canonical wire format when flags has FQDN_FLAG_E,
+    /// otherwise ASCII. A trailing dot marks a fully qualified name, otherwise it is partial.
+    ClientFqdn {
+        flags: u8,
+        rcode1: u8,
+        rcode2: u8,
+        name: String,
+    },
+    RelayAgentInformation(Vec<RelayAgentSubOption>),
+    /// RFC 3925 vendor classes by enterprise number, see vendor_class().
+    VendorIdentifyingVendorClass(Vec<VendorClass>),
+    /// RFC 3925 vendor sub-options by enterprise number, see vendor_options().
+    VendorIdentifyingVendorSpecific(Vec<VendorOptions>),
+    /// Option decoded by a type registered with register_extension.
+    Extension(Extension),
+    Unrecognized(RawDhcpOption),
+}
+
+/// Typed option defined outside this crate, for a code without a built-in decoder. Once
+/// registered with register_extension, it is decoded as DhcpOption::Extension instead of
+/// Unrecognized.
+pub trait ExtensionOption: Clone + PartialEq + fmt::Debug + Send + Sync + 'static {
+    const CODE: u8;
+    const TITLE: &'static str;
+
+    fn encode(&self) -> Vec<u8>;
+    fn decode(data: &[u8]) -> Result<Self, DecodeErrorKind>;
+}
+
+/// Value of a registered ExtensionOption.
+pub struct Extension(Box<dyn ErasedOption>);
+
+/// Object safe part of ExtensionOption, implemented for all its types.
+trait ErasedOption: fmt::Debug + Send + Sync {
+    fn code(&self) -> u8;
+    fn encode(&self) -> Vec<u8>;
+    fn as_any(&self) -> &dyn Any;
+    fn clone_box(&self) -> Box<dyn ErasedOption>;
+    fn eq_dyn(&self, other: &dyn ErasedOption) -> bool;
+}
+
+impl<T: ExtensionOption> ErasedOption for T {
+    fn code(&self) -> u8 {
+        T::CODE
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        ExtensionOption::encode(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn ErasedOption> {
+        Box::new(self.clone())
+    }
+
+    fn eq_dyn(&self, other: &dyn ErasedOption) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+impl Extension {
+    pub fn new<T: ExtensionOption>(value: T) -> Extension {
+        Extension(Box::new(value))
+    }
+
+    pub fn code(&self) -> u8 {
+        self.0.code()
+    }
+
+    /// Encodes the value as option data.
+    pub fn encode(&self) -> Vec<u8> {
+        self.0.encode()
+    }
+
+    /// Returns the value if it is of type T.
+    pub fn downcast_ref<T: ExtensionOption>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl Clone for Extension {
+    fn clone(&self) -> Extension {
+        Extension(self.0.clone_box())
+    }
+}
+
+impl PartialEq for Extension {
+    fn eq(&self, other: &Extension) -> bool {
+        self.0.eq_dyn(other.0.as_ref())
+    }
+}
+
+impl fmt::Debug for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+struct Registration {
+    code: u8,
+    title: &'static str,
+    decode: fn(&[u8]) -> Result<Extension, DecodeErrorKind>,
+}
+
+static EXTENSIONS: RwLock<Vec<Registration>> = RwLock::new(Vec::new());
+
+/// Bitmap of codes in EXTENSIONS, so unregistered codes are decoded without locking.
+static REGISTERED_CODES: [AtomicU64; 4] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+fn is_registered(code: u8) -> bool {
+    REGISTERED_CODES[code as usize / 64].load(Ordering::Acquire) & (1 << (code % 64)) != 0
+}
+
+/// Reason register_extension refused a type.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RegisterError {
+    /// The code is PAD, END or has a built-in decoder.
+    BuiltIn(u8),
+    /// Another type is already registered for the code.
+    AlreadyRegistered(u8),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegisterError::BuiltIn(code) => write!(f, "option {} has a built-in decoder", code),
+            RegisterError::AlreadyRegistered(code) => {
+                write!(f, "option {} already has a registered type", code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+fn decode_extension<T: ExtensionOption>(data: &[u8]) -> Result<Extension, DecodeErrorKind> {
+    Ok(Extension::new(T::decode(data)?))
+}
+
+/// Registers T to decode options with code T::CODE. Registrations are process wide, and a
+/// code can be registered only once.
+pub fn register_extension<T: ExtensionOption>() -> Result<(), RegisterError> {
+    if has_builtin_decoder(T::CODE) {
+        return Err(RegisterError::BuiltIn(T::CODE));
+    }
+    let mut extensions = EXTENSIONS.write().unwrap_or_else(|e| e.into_inner());
+    if extensions.iter().any(|r| r.code == T::CODE) {
+        return Err(RegisterError::AlreadyRegistered(T::CODE));
+    }
+    extensions.push(Registration {
+        code: T::CODE,
+        title: T::TITLE,
+        decode: decode_extension::<T>,
+    });
+    REGISTERED_CODES[T::CODE as usize / 64].fetch_or(1 << (T::CODE % 64), Ordering::Release);
+    Ok(())
+}
+
+/// Decodes data with the type registered for code, if any.
+pub(crate) fn decode_registered(
+    code: u8,
+    data: &[u8],
+) -> Option<Result<Extension, DecodeErrorKind>> {
+    if !is_registered(code) {
+        return None;
+    }
+    let extensions = EXTENSIONS.read().unwrap_or_else(|e| e.into_inner());
+    let registration = extensions.iter().find(|r| r.code == code)?;
+    Some((registration.decode)(data))
+}
+
+fn registered_title(code: u8) -> Option<&'static str> {
+    if !is_registered(code) {
+        return None;
+    }
+    let extensions = EXTENSIONS.read().unwrap_or_else(|e| e.into_inner());
+    extensions.iter().find(|r| r.code == code).map(|r| r.title)
+}
+
+/// Vendor class data of one enterprise in V_I_VENDOR_CLASS.
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VendorClass {
+    pub enterprise: u32,
+    /// Items longer than 254 bytes cannot be encoded.
+    pub data: Vec<Vec<u8>>,
+}
+
+/// Sub-options of one enterprise in V_I_VENDOR_SPECIFIC_INFORMATION.
+/// Sub-options longer than 253 bytes cannot be encoded.
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VendorOptions {
+    pub enterprise: u32,
+    pub options: Vec<RawDhcpOption>,
+}
+
+impl VendorOptions {
+    /// Returns the sub-option with the given code, if present.
+    pub fn option(&self, code: u8) -> Option<&RawDhcpOption> {
+        self.options.iter().find(|o| o.code == code)
+    }
+}
+
+/// DHCP Unique Identifier (RFC 8415 section 11), as used in RFC 4361 client identifiers.
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Duid {
+    LinkLayerTime {
+        htype: u16,
+        time: u32,
+        addr: Vec<u8>,
+    },
+    EnterpriseNumber {
+        enterprise: u32,
+        id: Vec<u8>,
+    },
+    LinkLayer {
+        htype: u16,
+        addr: Vec<u8>,
+    },
+    Uuid([u8; 16]),
+    /// Unknown DUID type, or a known type with the wrong length.
+    Unrecognized {
+        duid_type: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl Duid {
+    /// Parses a DUID, returning None if it is too short to have a type.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Duid> {
+        if bytes.len() < 2 {
+            return None;
+        }
+        let duid_type = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let data = &bytes[2..];
+        Some(match duid_type {
+            DUID_LLT if data.len() >= 6 => Duid::LinkLayerTime {
+                htype: u16::from_be_bytes([data[0], data[1]]),
+                time: u32::from_be_bytes([data[2], data[3], data[4], data[5]]),
+                addr: data[6..].to_vec(),
+            },
+            DUID_EN if data.len() >= 4 => Duid::EnterpriseNumber {
+                enterprise: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                id: data[4..].to_vec(),
+            },
+            DUID_LL if data.len() >= 2 => Duid::LinkLayer {
+                htype: u16::from_be_bytes([data[0], data[1]]),
+                addr: data[2..].to_vec(),
+            },
+            DUID_UUID if data.len() == 16 => {
+                let mut uuid = [0; 16];
+                uuid.copy_from_slice(data);
+                Duid::Uuid(uuid)
+            }
+            _ => Duid::Unrecognized {
+                duid_type,
+                data: data.to_vec(),
+            },
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = vec![];
+        match self {
+            Duid::LinkLayerTime { htype, time, addr } => {
+                v.extend(&DUID_LLT.to_be_bytes());
+                v.extend(&htype.to_be_bytes());
+                v.extend(&time.to_be_bytes());
+                v.extend(addr);
+            }
+            Duid::EnterpriseNumber { enterprise, id } => {
+                v.extend(&DUID_EN.to_be_bytes());
+                v.extend(&enterprise.to_be_bytes());
+                v.extend(id);
+            }
+            Duid::LinkLayer { htype, addr } => {
+                v.extend(&DUID_LL.to_be_bytes());
+                v.extend(&htype.to_be_bytes());
+                v.extend(addr);
+            }
+            Duid::Uuid(uuid) => {
+                v.extend(&DUID_UUID.to_be_bytes());
+                v.extend(uuid);
+            }
+            Duid::Unrecognized { duid_type, data } => {
+                v.extend(&duid_type.to_be_bytes());
+                v.extend(data);
+            }
+        }
+        v
+    }
+}
+
+/// Sub-option of RELAY_AGENT_INFORMATION (RFC 3046), added by a relay agent and echoed
+/// back unchanged by the server.
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RelayAgentSubOption {
+    CircuitId(Vec<u8>),
+    RemoteId(Vec<u8>),
+    /// RFC 3527 subnet from which to allocate, in place of giaddr.
+    LinkSelection(Ipv4Addr),
+    /// RFC 3993 subscriber identifier, NVT ASCII.
+    SubscriberId(String),
+    /// RFC 5010 flags, see RELAY_AGENT_FLAG_UNICAST.
+    RelayAgentFlags(u8),
+    /// RFC 5107 address the client should use as server identifier.
+    ServerIdentifierOverride(Ipv4Addr),
+    /// RFC 6607 VSS type and information.
+    VirtualSubnetSelection {
+        vss_type: u8,
+        info: Vec<u8>,
+    },
+    /// RFC 6607 VSS control, which has no data.
+    VirtualSubnetSelectionControl,
     Unrecognized(RawDhcpOption),
 }
 
+impl RelayAgentSubOption {
+    pub fn to_raw(&self) -> RawDhcpOption {
+        match self {
+            Self::CircuitId(id) => RawDhcpOption {
+                code: RELAY_AGENT_CIRCUIT_ID,
+                data: id.clone(),
+            },
+            Self::RemoteId(id) => RawDhcpOption {
+                code: RELAY_AGENT_REMOTE_ID,
+                data: id.clone(),
+            },
+            Self::LinkSelection(addr) => RawDhcpOption {
+                code: RELAY_AGENT_LINK_SELECTION,
+                data: addr.octets().to_vec(),
+            },
+            Self::SubscriberId(id) => RawDhcpOption {
+                code: RELAY_AGENT_SUBSCRIBER_ID,
+                data: id.as_bytes().to_vec(),
+            },
+            Self::RelayAgentFlags(flags) => RawDhcpOption {
+                code: RELAY_AGENT_FLAGS,
+                data: vec![*flags],
+            },
+            Self::ServerIdentifierOverride(addr) => RawDhcpOption {
+                code: RELAY_AGENT_SERVER_IDENTIFIER_OVERRIDE,
+                data: addr.octets().to_vec(),
+            },
+            Self::VirtualSubnetSelection { vss_type, info } => RawDhcpOption {
+                code: RELAY_AGENT_VIRTUAL_SUBNET_SELECTION,
+                data: {
+                    let mut v = vec![*vss_type];
+                    v.extend(info);
+                    v
+                },
+            },
+            Self::VirtualSubnetSelectionControl => RawDhcpOption {
+                code: RELAY_AGENT_VIRTUAL_SUBNET_SELECTION_CONTROL,
+                data: vec![],
+            },
+            Self::Unrecognized(raw) => raw.clone(),
+        }
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::CircuitId(_) => RELAY_AGENT_CIRCUIT_ID,
+            Self::RemoteId(_) => RELAY_AGENT_REMOTE_ID,
+            Self::LinkSelection(_) => RELAY_AGENT_LINK_SELECTION,
+            Self::SubscriberId(_) => RELAY_AGENT_SUBSCRIBER_ID,
+            Self::RelayAgentFlags(_) => RELAY_AGENT_FLAGS,
+            Self::ServerIdentifierOverride(_) => RELAY_AGENT_SERVER_IDENTIFIER_OVERRIDE,
+            Self::VirtualSubnetSelection { .. } => RELAY_AGENT_VIRTUAL_SUBNET_SELECTION,
+            Self::VirtualSubnetSelectionControl => RELAY_AGENT_VIRTUAL_SUBNET_SELECTION_CONTROL,
+            Self::Unrecognized(x) => x.code,
+        }
+    }
+}
+
 impl DhcpOption {
+    /// Returns the vendor class data of enterprise, if this is VendorIdentifyingVendorClass.
+    pub fn vendor_class(&self, enterprise: u32) -> Option<&VendorClass> {
+        match self {
+            DhcpOption::VendorIdentifyingVendorClass(classes) => {
+                classes.iter().find(|c| c.enterprise == enterprise)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the sub-options of enterprise, if this is VendorIdentifyingVendorSpecific.
+    pub fn vendor_options(&self, enterprise: u32) -> Option<&VendorOptions> {
+        match self {
+            DhcpOption::VendorIdentifyingVendorSpecific(vendors) => {
+                vendors.iter().find(|v| v.enterprise == enterprise)
+            }
+            _ => None,
+        }
+    }
+
+    /// Creates an RFC 4361 CLIENT_IDENTIFIER from an IAID and DUID.
+    pub fn rfc4361_client_identifier(iaid: u32, duid: &Duid) -> DhcpOption {
+        let mut id = iaid.to_be_bytes().to_vec();
+        id.extend(duid.to_bytes());
+        DhcpOption::ClientIdentifier {
+            htype: CLIENT_ID_RFC4361,
+            id,
+        }
+    }
+
+    /// Returns the IAID and DUID of an RFC 4361 CLIENT_IDENTIFIER.
+    pub fn iaid_duid(&self) -> Option<(u32, Duid)> {
+        match self {
+            DhcpOption::ClientIdentifier {
+                htype: CLIENT_ID_RFC4361,
+                id,
+            } if id.len() >= 4 => Some((
+                u32::from_be_bytes([id[0], id[1], id[2], id[3]]),
+                Duid::from_bytes(&id[4..])?,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Encodes the option, failing if an item within it is too long for its length byte.
+    pub fn try_to_raw(&self) -> Result<RawDhcpOption, EncodeError> {
+        match self.oversized_item() {
+            Some(len) => Err(EncodeError::ItemTooLong {
+                option: self.code(),
+                len,
+            }),
+            None => Ok(self.to_raw()),
+        }
+    }
+
+    /// Length of the first item too long for its length byte, if any.
+    fn oversized_item(&self) -> Option<usize> {
+        let (lens, max): (Vec<usize>, usize) = match self {
+            Self::UserClass(classes) => (classes.iter().map(Vec::len).collect(), 255),
+            Self::RelayAgentInformation(subs) => {
+                (subs.iter().map(|s| s.to_raw().data.len()).collect(), 255)
+            }
+            Self::VendorIdentifyingVendorClass(classes) => (
+                classes.iter().flat_map(|c| &c.data).map(Vec::len).collect(),
+                254,
+            ),
+            Self::VendorIdentifyingVendorSpecific(vendors) => (
+                vendors
+                    .iter()
+                    .flat_map(|v| &v.options)
+                    .map(|o| o.data.len())
+                    .collect(),
+                253,
+            ),
+            _ => return None,
+        };
+        lens.into_iter().find(|&len| len > max)
+    }
+
+    /// Encodes the option. Items too long for their length byte are truncated; use try_to_raw
+    /// to reject them instead.
     pub fn to_raw(&self) -> RawDhcpOption {
         match self {
+            Self::SubnetMask(addr) => RawDhcpOption {
+                code: SUBNET_MASK,
+                data: addr.octets().to_vec(),
+            },
+            Self::TimeOffset(v) => RawDhcpOption {
+                code: TIME_OFFSET,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::Router(addrs) => RawDhcpOption {
+                code: ROUTER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::TimeServer(addrs) => RawDhcpOption {
+                code: TIME_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NameServer(addrs) => RawDhcpOption {
+                code: NAME_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::DomainNameServer(addrs) => RawDhcpOption {
+                code: DOMAIN_NAME_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::LogServer(addrs) => RawDhcpOption {
+                code: LOG_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::CookieServer(addrs) => RawDhcpOption {
+                code: COOKIE_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::LprServer(addrs) => RawDhcpOption {
+                code: LPR_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::ImpressServer(addrs) => RawDhcpOption {
+                code: IMPRESS_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::ResourceLocationServer(addrs) => RawDhcpOption {
+                code: RESOURCE_LOCATION_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::HostName(s) => RawDhcpOption {
+                code: HOST_NAME,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::BootFileSize(v) => RawDhcpOption {
+                code: BOOT_FILE_SIZE,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::MeritDumpFile(s) => RawDhcpOption {
+                code: MERIT_DUMP_FILE,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::DomainName(s) => RawDhcpOption {
+                code: DOMAIN_NAME,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::SwapServer(addr) => RawDhcpOption {
+                code: SWAP_SERVER,
+                data: addr.octets().to_vec(),
+            },
+            Self::RootPath(s) => RawDhcpOption {
+                code: ROOT_PATH,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::ExtensionsPath(s) => RawDhcpOption {
+                code: EXTENSIONS_PATH,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::IpForwardingEnableDisable(on) => RawDhcpOption {
+                code: IP_FORWARDING_ENABLE_DISABLE,
+                data: vec![*on as u8],
+            },
+            Self::NonLocalSourceRoutingEnableDisable(on) => RawDhcpOption {
+                code: NON_LOCAL_SOURCE_ROUTING_ENABLE_DISABLE,
+                data: vec![*on as u8],
+            },
+            Self::PolicyFilter(pairs) => RawDhcpOption {
+                code: POLICY_FILTER,
+                data: ipv4_pairs_bytes(pairs),
+            },
+            Self::MaximumDatagramReassemblySize(v) => RawDhcpOption {
+                code: MAXIMUM_DATAGRAM_REASSEMBLY_SIZE,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::DefaultIpTimeToLive(v) => RawDhcpOption {
+                code: DEFAULT_IP_TIME_TO_LIVE,
+                data: vec![*v],
+            },
+            Self::PathMtuAgingTimeout(v) => RawDhcpOption {
+                code: PATH_MTU_AGING_TIMEOUT,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::PathMtuPlateauTable(sizes) => RawDhcpOption {
+                code: PATH_MTU_PLATEAU_TABLE,
+                data: sizes
+                    .iter()
+                    .flat_map(|s| s.to_be_bytes().to_vec())
+                    .collect(),
+            },
+            Self::InterfaceMtu(v) => RawDhcpOption {
+                code: INTERFACE_MTU,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::AllSubnetsAreLocal(on) => RawDhcpOption {
+                code: ALL_SUBNETS_ARE_LOCAL,
+                data: vec![*on as u8],
+            },
+            Self::BroadcastAddress(addr) => RawDhcpOption {
+                code: BROADCAST_ADDRESS,
+                data: addr.octets().to_vec(),
+            },
+            Self::PerformMaskDiscovery(on) => RawDhcpOption {
+                code: PERFORM_MASK_DISCOVERY,
+                data: vec![*on as u8],
+            },
+            Self::MaskSupplier(on) => RawDhcpOption {
+                code: MASK_SUPPLIER,
+                data: vec![*on as u8],
+            },
+            Self::PerformRouterDiscovery(on) => RawDhcpOption {
+                code: PERFORM_ROUTER_DISCOVERY,
+                data: vec![*on as u8],
+            },
+            Self::RouterSolicitationAddress(addr) => RawDhcpOption {
+                code: ROUTER_SOLICITATION_ADDRESS,
+                data: addr.octets().to_vec(),
+            },
+            Self::StaticRoute(pairs) => RawDhcpOption {
+                code: STATIC_ROUTE,
+                data: ipv4_pairs_bytes(pairs),
+            },
+            Self::TrailerEncapsulation(on) => RawDhcpOption {
+                code: TRAILER_ENCAPSULATION,
+                data: vec![*on as u8],
+            },
+            Self::ArpCacheTimeout(v) => RawDhcpOption {
+                code: ARP_CACHE_TIMEOUT,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::EthernetEncapsulation(on) => RawDhcpOption {
+                code: ETHERNET_ENCAPSULATION,
+                data: vec![*on as u8],
+            },
+            Self::TcpDefaultTtl(v) => RawDhcpOption {
+                code: TCP_DEFAULT_TTL,
+                data: vec![*v],
+            },
+            Self::TcpKeepaliveInterval(v) => RawDhcpOption {
+                code: TCP_KEEPALIVE_INTERVAL,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::TcpKeepaliveGarbage(on) => RawDhcpOption {
+                code: TCP_KEEPALIVE_GARBAGE,
+                data: vec![*on as u8],
+            },
+            Self::NetworkInformationServiceDomain(s) => RawDhcpOption {
+                code: NETWORK_INFORMATION_SERVICE_DOMAIN,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::NetworkInformationServers(addrs) => RawDhcpOption {
+                code: NETWORK_INFORMATION_SERVERS,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NetworkTimeProtocolServers(addrs) => RawDhcpOption {
+                code: NETWORK_TIME_PROTOCOL_SERVERS,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NetbiosOverTcpipNameServer(addrs) => RawDhcpOption {
+                code: NETBIOS_OVER_TCPIP_NAME_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NetbiosOverTcpipDatagramDistributionServer(addrs) => RawDhcpOption {
+                code: NETBIOS_OVER_TCPIP_DATAGRAM_DISTRIBUTION_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NetbiosOverTcpipNodeType(v) => RawDhcpOption {
+                code: NETBIOS_OVER_TCPIP_NODE_TYPE,
+                data: vec![*v],
+            },
+            Self::NetbiosOverTcpipScope(s) => RawDhcpOption {
+                code: NETBIOS_OVER_TCPIP_SCOPE,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::XwindowSystemFontServer(addrs) => RawDhcpOption {
+                code: XWINDOW_SYSTEM_FONT_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::XwindowSystemDisplayManager(addrs) => RawDhcpOption {
+                code: XWINDOW_SYSTEM_DISPLAY_MANAGER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NetworkInformationServiceplusDomain(s) => RawDhcpOption {
+                code: NETWORK_INFORMATION_SERVICEPLUS_DOMAIN,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::NetworkInformationServiceplusServers(addrs) => RawDhcpOption {
+                code: NETWORK_INFORMATION_SERVICEPLUS_SERVERS,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::MobileIpHomeAgent(addrs) => RawDhcpOption {
+                code: MOBILE_IP_HOME_AGENT,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::SimpleMailTransportProtocol(addrs) => RawDhcpOption {
+                code: SIMPLE_MAIL_TRANSPORT_PROTOCOL,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::PostOfficeProtocolServer(addrs) => RawDhcpOption {
+                code: POST_OFFICE_PROTOCOL_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::NetworkNewsTransportProtocol(addrs) => RawDhcpOption {
+                code: NETWORK_NEWS_TRANSPORT_PROTOCOL,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::DefaultWorldWideWebServer(addrs) => RawDhcpOption {
+                code: DEFAULT_WORLD_WIDE_WEB_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::DefaultFingerServer(addrs) => RawDhcpOption {
+                code: DEFAULT_FINGER_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::DefaultInternetRelayChatServer(addrs) => RawDhcpOption {
+                code: DEFAULT_INTERNET_RELAY_CHAT_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::StreettalkServer(addrs) => RawDhcpOption {
+                code: STREETTALK_SERVER,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::StreettalkDirectoryAssistance(addrs) => RawDhcpOption {
+                code: STREETTALK_DIRECTORY_ASSISTANCE,
+                data: ipv4s_bytes(addrs),
+            },
+            Self::VendorSpecificInformation(data) => RawDhcpOption {
+                code: VENDOR_SPECIFIC_INFORMATION,
+                data: data.clone(),
+            },
+            Self::RequestedIpAddress(addr) => RawDhcpOption {
+                code: REQUESTED_IP_ADDRESS,
+                data: addr.octets().to_vec(),
+            },
+            Self::IpAddressLeaseTime(v) => RawDhcpOption {
+                code: IP_ADDRESS_LEASE_TIME,
+                data: v.to_be_bytes().to_vec(),
+            },
             Self::DhcpMessageType(mtype) => RawDhcpOption {
                 code: DHCP_MESSAGE_TYPE,
                 data: vec![*mtype as u8],
@@ -33,49 +855,143 @@ impl DhcpOption {
                 code: SERVER_IDENTIFIER,
                 data: addr.octets().to_vec(),
             },
-            Self::ParameterRequestList(prl) => RawDhcpOption {
+            Self::ParameterRequestList(data) => RawDhcpOption {
                 code: PARAMETER_REQUEST_LIST,
-                data: prl.clone(),
+                data: data.clone(),
             },
-            Self::RequestedIpAddress(addr) => RawDhcpOption {
-                code: REQUESTED_IP_ADDRESS,
-                data: addr.octets().to_vec(),
+            Self::Message(s) => RawDhcpOption {
+                code: MESSAGE,
+                data: s.as_bytes().to_vec(),
             },
-            Self::HostName(name) => RawDhcpOption {
-                code: HOST_NAME,
-                data: name.as_bytes().to_vec(),
+            Self::MaximumDhcpMessageSize(v) => RawDhcpOption {
+                code: MAXIMUM_DHCP_MESSAGE_SIZE,
+                data: v.to_be_bytes().to_vec(),
             },
-            Self::Router(addrs) => RawDhcpOption {
-                code: ROUTER,
+            Self::RenewalTimeValue(v) => RawDhcpOption {
+                code: RENEWAL_TIME_VALUE,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::RebindingTimeValue(v) => RawDhcpOption {
+                code: REBINDING_TIME_VALUE,
+                data: v.to_be_bytes().to_vec(),
+            },
+            Self::VendorClassIdentifier(data) => RawDhcpOption {
+                code: VENDOR_CLASS_IDENTIFIER,
+                data: data.clone(),
+            },
+            Self::ClientIdentifier { htype, id } => RawDhcpOption {
+                code: CLIENT_IDENTIFIER,
+                data: {
+                    let mut v = vec![*htype];
+                    v.extend(id);
+                    v
+                },
+            },
+            Self::TftpServerName(s) => RawDhcpOption {
+                code: TFTP_SERVER_NAME,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::BootfileName(s) => RawDhcpOption {
+                code: BOOTFILE_NAME,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::TzPosixString(s) => RawDhcpOption {
+                code: TZ_POSIX_STRING,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::TzDatabaseString(s) => RawDhcpOption {
+                code: TZ_DATABASE_STRING,
+                data: s.as_bytes().to_vec(),
+            },
+            Self::DomainSearch(names) => RawDhcpOption {
+                code: DOMAIN_SEARCH,
+                data: domain_names_bytes(names),
+            },
+            Self::ClientArchitecture(archs) => RawDhcpOption {
+                code: CLIENT_ARCHITECTURE,
+                data: archs
+                    .iter()
+                    .flat_map(|a| u16::from(*a).to_be_bytes().to_vec())
+                    .collect(),
+            },
+            Self::ClasslessStaticRoute(routes) => RawDhcpOption {
+                code: CLASSLESS_ROUTE_FORMAT,
+                data: classless_routes_bytes(routes),
+            },
+            Self::MsClasslessStaticRoute(routes) => RawDhcpOption {
+                code: MS_CLASSLESS_ROUTE_FORMAT,
+                data: classless_routes_bytes(routes),
+            },
+            Self::UserClass(classes) => RawDhcpOption {
+                code: USER_CLASS,
                 data: {
                     let mut v = vec![];
-                    for a in addrs {
-                        v.extend(a.octets().iter());
+                    for class in classes {
+                        let class = &class[..class.len().min(255)];
+                        v.push(class.len() as u8);
+                        v.extend(class);
                     }
                     v
                 },
             },
-            Self::DomainNameServer(addrs) => RawDhcpOption {
-                code: DOMAIN_NAME_SERVER,
+            Self::ClientFqdn {
+                flags,
+                rcode1,
+                rcode2,
+                name,
+            } => RawDhcpOption {
+                code: CLIENT_FQDN,
+                data: {
+                    let mut v = vec![*flags, *rcode1, *rcode2];
+                    if flags & FQDN_FLAG_E != 0 {
+                        v.extend(fqdn_name_bytes(name));
+                    } else {
+                        v.extend(name.as_bytes());
+                    }
+                    v
+                },
+            },
+            Self::RelayAgentInformation(subs) => RawDhcpOption {
+                code: RELAY_AGENT_INFORMATION,
                 data: {
                     let mut v = vec![];
-                    for a in addrs {
-                        v.extend(a.octets().iter());
+                    for sub in subs {
+                        let raw = sub.to_raw();
+                        let data = &raw.data[..raw.data.len().min(255)];
+                        v.push(raw.code);
+                        v.push(data.len() as u8);
+                        v.extend(data);
                     }
                     v
                 },
             },
-            Self::IpAddressLeaseTime(secs) => RawDhcpOption {
-                code: IP_ADDRESS_LEASE_TIME,
-                data: secs.to_be_bytes().to_vec(),
+            Self::VendorIdentifyingVendorClass(classes) => RawDhcpOption {
+                code: V_I_VENDOR_CLASS,
+                data: enterprise_blocks_bytes(classes.iter().map(|c| {
+                    let items = c.data.iter().map(|d| {
+                        let d = &d[..d.len().min(254)];
+                        let mut v = vec![d.len() as u8];
+                        v.extend(d);
+                        v
+                    });
+                    (c.enterprise, items.collect())
+                })),
             },
-            Self::SubnetMask(mask) => RawDhcpOption {
-                code: SUBNET_MASK,
-                data: mask.octets().to_vec(),
+            Self::VendorIdentifyingVendorSpecific(vendors) => RawDhcpOption {
+                code: V_I_VENDOR_SPECIFIC_INFORMATION,
+                data: enterprise_blocks_bytes(vendors.iter().map(|v| {
+                    let items = v.options.iter().map(|o| {
+                        let data = &o.data[..o.data.len().min(253)];
+                        let mut v = vec![o.code, data.len() as u8];
+                        v.extend(data);
+                        v
+                    });
+                    (v.enterprise, items.collect())
+                })),
             },
-            Self::Message(msg) => RawDhcpOption {
-                code: MESSAGE,
-                data: msg.as_bytes().to_vec(),
+            Self::Extension(ext) => RawDhcpOption {
+                code: ext.code(),
+                data: ext.encode(),
             },
             Self::Unrecognized(raw) => raw.clone(),
         }
@@ -83,21 +999,426 @@ impl DhcpOption {
 
     pub fn code(&self) -> u8 {
         match self {
-            Self::DhcpMessageType(_) => DHCP_MESSAGE_TYPE,
-            Self::ServerIdentifier(_) => SERVER_IDENTIFIER,
-            Self::ParameterRequestList(_) => PARAMETER_REQUEST_LIST,
-            Self::RequestedIpAddress(_) => REQUESTED_IP_ADDRESS,
-            Self::HostName(_) => HOST_NAME,
+            Self::SubnetMask(_) => SUBNET_MASK,
+            Self::TimeOffset(_) => TIME_OFFSET,
             Self::Router(_) => ROUTER,
+            Self::TimeServer(_) => TIME_SERVER,
+            Self::NameServer(_) => NAME_SERVER,
             Self::DomainNameServer(_) => DOMAIN_NAME_SERVER,
+            Self::LogServer(_) => LOG_SERVER,
+            Self::CookieServer(_) => COOKIE_SERVER,
+            Self::LprServer(_) => LPR_SERVER,
+            Self::ImpressServer(_) => IMPRESS_SERVER,
+            Self::ResourceLocationServer(_) => RESOURCE_LOCATION_SERVER,
+            Self::HostName(_) => HOST_NAME,
+            Self::BootFileSize(_) => BOOT_FILE_SIZE,
+            Self::MeritDumpFile(_) => MERIT_DUMP_FILE,
+            Self::DomainName(_) => DOMAIN_NAME,
+            Self::SwapServer(_) => SWAP_SERVER,
+            Self::RootPath(_) => ROOT_PATH,
+            Self::ExtensionsPath(_) => EXTENSIONS_PATH,
+            Self::IpForwardingEnableDisable(_) => IP_FORWARDING_ENABLE_DISABLE,
+            Self::NonLocalSourceRoutingEnableDisable(_) => NON_LOCAL_SOURCE_ROUTING_ENABLE_DISABLE,
+            Self::PolicyFilter(_) => POLICY_FILTER,
+            Self::MaximumDatagramReassemblySize(_) => MAXIMUM_DATAGRAM_REASSEMBLY_SIZE,
+            Self::DefaultIpTimeToLive(_) => DEFAULT_IP_TIME_TO_LIVE,
+            Self::PathMtuAgingTimeout(_) => PATH_MTU_AGING_TIMEOUT,
+            Self::PathMtuPlateauTable(_) => PATH_MTU_PLATEAU_TABLE,
+            Self::InterfaceMtu(_) => INTERFACE_MTU,
+            Self::AllSubnetsAreLocal(_) => ALL_SUBNETS_ARE_LOCAL,
+            Self::BroadcastAddress(_) => BROADCAST_ADDRESS,
+            Self::PerformMaskDiscovery(_) => PERFORM_MASK_DISCOVERY,
+            Self::MaskSupplier(_) => MASK_SUPPLIER,
+            Self::PerformRouterDiscovery(_) => PERFORM_ROUTER_DISCOVERY,
+            Self::RouterSolicitationAddress(_) => ROUTER_SOLICITATION_ADDRESS,
+            Self::StaticRoute(_) => STATIC_ROUTE,
+            Self::TrailerEncapsulation(_) => TRAILER_ENCAPSULATION,
+            Self::ArpCacheTimeout(_) => ARP_CACHE_TIMEOUT,
+            Self::EthernetEncapsulation(_) => ETHERNET_ENCAPSULATION,
+            Self::TcpDefaultTtl(_) => TCP_DEFAULT_TTL,
+            Self::TcpKeepaliveInterval(_) => TCP_KEEPALIVE_INTERVAL,
+            Self::TcpKeepaliveGarbage(_) => TCP_KEEPALIVE_GARBAGE,
+            Self::NetworkInformationServiceDomain(_) => NETWORK_INFORMATION_SERVICE_DOMAIN,
+            Self::NetworkInformationServers(_) => NETWORK_INFORMATION_SERVERS,
+            Self::NetworkTimeProtocolServers(_) => NETWORK_TIME_PROTOCOL_SERVERS,
+            Self::NetbiosOverTcpipNameServer(_) => NETBIOS_OVER_TCPIP_NAME_SERVER,
+            Self::NetbiosOverTcpipDatagramDistributionServer(_) => {
+                NETBIOS_OVER_TCPIP_DATAGRAM_DISTRIBUTION_SERVER
+            }
+            Self::NetbiosOverTcpipNodeType(_) => NETBIOS_OVER_TCPIP_NODE_TYPE,
+            Self::NetbiosOverTcpipScope(_) => NETBIOS_OVER_TCPIP_SCOPE,
+            Self::XwindowSystemFontServer(_) => XWINDOW_SYSTEM_FONT_SERVER,
+            Self::XwindowSystemDisplayManager(_) => XWINDOW_SYSTEM_DISPLAY_MANAGER,
+            Self::NetworkInformationServiceplusDomain(_) => NETWORK_INFORMATION_SERVICEPLUS_DOMAIN,
+            Self::NetworkInformationServiceplusServers(_) => {
+                NETWORK_INFORMATION_SERVICEPLUS_SERVERS
+            }
+            Self::MobileIpHomeAgent(_) => MOBILE_IP_HOME_AGENT,
+            Self::SimpleMailTransportProtocol(_) => SIMPLE_MAIL_TRANSPORT_PROTOCOL,
+            Self::PostOfficeProtocolServer(_) => POST_OFFICE_PROTOCOL_SERVER,
+            Self::NetworkNewsTransportProtocol(_) => NETWORK_NEWS_TRANSPORT_PROTOCOL,
+            Self::DefaultWorldWideWebServer(_) => DEFAULT_WORLD_WIDE_WEB_SERVER,
+            Self::DefaultFingerServer(_) => DEFAULT_FINGER_SERVER,
+            Self::DefaultInternetRelayChatServer(_) => DEFAULT_INTERNET_RELAY_CHAT_SERVER,
+            Self::StreettalkServer(_) => STREETTALK_SERVER,
+            Self::StreettalkDirectoryAssistance(_) => STREETTALK_DIRECTORY_ASSISTANCE,
+            Self::VendorSpecificInformation(_) => VENDOR_SPECIFIC_INFORMATION,
+            Self::RequestedIpAddress(_) => REQUESTED_IP_ADDRESS,
             Self::IpAddressLeaseTime(_) => IP_ADDRESS_LEASE_TIME,
-            Self::SubnetMask(_) => SUBNET_MASK,
+            Self::DhcpMessageType(_) => DHCP_MESSAGE_TYPE,
+            Self::ServerIdentifier(_) => SERVER_IDENTIFIER,
+            Self::ParameterRequestList(_) => PARAMETER_REQUEST_LIST,
             Self::Message(_) => MESSAGE,
+            Self::MaximumDhcpMessageSize(_) => MAXIMUM_DHCP_MESSAGE_SIZE,
+            Self::RenewalTimeValue(_) => RENEWAL_TIME_VALUE,
+            Self::RebindingTimeValue(_) => REBINDING_TIME_VALUE,
+            Self::VendorClassIdentifier(_) => VENDOR_CLASS_IDENTIFIER,
+            Self::ClientIdentifier { .. } => CLIENT_IDENTIFIER,
+            Self::TftpServerName(_) => TFTP_SERVER_NAME,
+            Self::BootfileName(_) => BOOTFILE_NAME,
+            Self::TzPosixString(_) => TZ_POSIX_STRING,
+            Self::TzDatabaseString(_) => TZ_DATABASE_STRING,
+            Self::DomainSearch(_) => DOMAIN_SEARCH,
+            Self::ClientArchitecture(_) => CLIENT_ARCHITECTURE,
+            Self::ClasslessStaticRoute(_) => CLASSLESS_ROUTE_FORMAT,
+            Self::MsClasslessStaticRoute(_) => MS_CLASSLESS_ROUTE_FORMAT,
+            Self::UserClass(_) => USER_CLASS,
+            Self::ClientFqdn { .. } => CLIENT_FQDN,
+            Self::RelayAgentInformation(_) => RELAY_AGENT_INFORMATION,
+            Self::VendorIdentifyingVendorClass(_) => V_I_VENDOR_CLASS,
+            Self::VendorIdentifyingVendorSpecific(_) => V_I_VENDOR_SPECIFIC_INFORMATION,
+            Self::Extension(ext) => ext.code(),
             Self::Unrecognized(x) => x.code,
         }
     }
 }
 
+/// Displays the option's title and value, such as "Router: 192.168.0.1, 192.168.0.2".
+impl fmt::Display for DhcpOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match title(self.code()) {
+            Some(title) => write!(f, "{}: {}", title, self.value()),
+            None => write!(f, "Option {}: {}", self.code(), self.value()),
+        }
+    }
+}
+
+impl DhcpOption {
+    /// Returns a Display of the option's decoded value, or its data in hex if unrecognized.
+    pub fn value(&self) -> OptionValue<'_> {
+        OptionValue(self)
+    }
+}
+
+pub struct OptionValue<'a>(&'a DhcpOption);
+
+impl fmt::Display for OptionValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            DhcpOption::SubnetMask(addr)
+            | DhcpOption::SwapServer(addr)
+            | DhcpOption::BroadcastAddress(addr)
+            | DhcpOption::RouterSolicitationAddress(addr)
+            | DhcpOption::RequestedIpAddress(addr)
+            | DhcpOption::ServerIdentifier(addr) => write!(f, "{}", addr),
+            DhcpOption::Router(addrs)
+            | DhcpOption::TimeServer(addrs)
+            | DhcpOption::NameServer(addrs)
+            | DhcpOption::DomainNameServer(addrs)
+            | DhcpOption::LogServer(addrs)
+            | DhcpOption::CookieServer(addrs)
+            | DhcpOption::LprServer(addrs)
+            | DhcpOption::ImpressServer(addrs)
+            | DhcpOption::ResourceLocationServer(addrs)
+            | DhcpOption::NetworkInformationServers(addrs)
+            | DhcpOption::NetworkTimeProtocolServers(addrs)
+            | DhcpOption::NetbiosOverTcpipNameServer(addrs)
+            | DhcpOption::NetbiosOverTcpipDatagramDistributionServer(addrs)
+            | DhcpOption::XwindowSystemFontServer(addrs)
+            | DhcpOption::XwindowSystemDisplayManager(addrs)
+            | DhcpOption::NetworkInformationServiceplusServers(addrs)
+            | DhcpOption::MobileIpHomeAgent(addrs)
+            | DhcpOption::SimpleMailTransportProtocol(addrs)
+            | DhcpOption::PostOfficeProtocolServer(addrs)
+            | DhcpOption::NetworkNewsTransportProtocol(addrs)
+            | DhcpOption::DefaultWorldWideWebServer(addrs)
+            | DhcpOption::DefaultFingerServer(addrs)
+            | DhcpOption::DefaultInternetRelayChatServer(addrs)
+            | DhcpOption::StreettalkServer(addrs)
+            | DhcpOption::StreettalkDirectoryAssistance(addrs) => write_list(f, addrs),
+            DhcpOption::HostName(s)
+            | DhcpOption::MeritDumpFile(s)
+            | DhcpOption::DomainName(s)
+            | DhcpOption::RootPath(s)
+            | DhcpOption::ExtensionsPath(s)
+            | DhcpOption::NetworkInformationServiceDomain(s)
+            | DhcpOption::NetbiosOverTcpipScope(s)
+            | DhcpOption::NetworkInformationServiceplusDomain(s)
+            | DhcpOption::Message(s)
+            | DhcpOption::TftpServerName(s)
+            | DhcpOption::BootfileName(s)
+            | DhcpOption::TzPosixString(s)
+            | DhcpOption::TzDatabaseString(s) => write!(f, "{}", s),
+            DhcpOption::IpForwardingEnableDisable(on)
+            | DhcpOption::NonLocalSourceRoutingEnableDisable(on)
+            | DhcpOption::AllSubnetsAreLocal(on)
+            | DhcpOption::PerformMaskDiscovery(on)
+            | DhcpOption::MaskSupplier(on)
+            | DhcpOption::PerformRouterDiscovery(on)
+            | DhcpOption::TrailerEncapsulation(on)
+            | DhcpOption::EthernetEncapsulation(on)
+            | DhcpOption::TcpKeepaliveGarbage(on) => write!(f, "{}", on),
+            DhcpOption::DefaultIpTimeToLive(v)
+            | DhcpOption::TcpDefaultTtl(v)
+            | DhcpOption::NetbiosOverTcpipNodeType(v) => write!(f, "{}", v),
+            DhcpOption::BootFileSize(v)
+            | DhcpOption::MaximumDatagramReassemblySize(v)
+            | DhcpOption::InterfaceMtu(v)
+            | DhcpOption::MaximumDhcpMessageSize(v) => write!(f, "{}", v),
+            DhcpOption::PathMtuAgingTimeout(v)
+            | DhcpOption::ArpCacheTimeout(v)
+            | DhcpOption::TcpKeepaliveInterval(v)
+            | DhcpOption::IpAddressLeaseTime(v)
+            | DhcpOption::RenewalTimeValue(v)
+            | DhcpOption::RebindingTimeValue(v) => write!(f, "{}", v),
+            DhcpOption::TimeOffset(v) => write!(f, "{}", v),
+            DhcpOption::PathMtuPlateauTable(sizes) => write_list(f, sizes),
+            DhcpOption::PolicyFilter(pairs) => {
+                let filters: Vec<_> = pairs.iter().map(|(a, m)| format!("{}/{}", a, m)).collect();
+                write_list(f, &filters)
+            }
+            DhcpOption::StaticRoute(pairs) => {
+                let routes: Vec<_> = pairs
+                    .iter()
+                    .map(|(d, r)| format!("{} via {}", d, r))
+                    .collect();
+                write_list(f, &routes)
+            }
+            DhcpOption::ClasslessStaticRoute(routes)
+            | DhcpOption::MsClasslessStaticRoute(routes) => {
+                let routes: Vec<_> = routes
+                    .iter()
+                    .map(|(d, r)| format!("{} via {}", d, r))
+                    .collect();
+                write_list(f, &routes)
+            }
+            DhcpOption::DhcpMessageType(mtype) => write!(f, "{}", mtype),
+            DhcpOption::ParameterRequestList(codes) => {
+                let params: Vec<_> = codes
+                    .iter()
+                    .map(|&c| match title(c) {
+                        Some(t) => format!("{} ({})", c, t),
+                        None => c.to_string(),
+                    })
+                    .collect();
+                write_list(f, &params)
+            }
+            DhcpOption::VendorClassIdentifier(data) => write_text(f, data),
+            DhcpOption::ClientIdentifier { htype, id } => match self.0.iaid_duid() {
+                Some((iaid, duid)) => {
+                    write!(f, "IAID {:08x}, DUID ", iaid)?;
+                    write_hex(f, &duid.to_bytes())
+                }
+                None => {
+                    write!(f, "type {}, ", htype)?;
+                    write_hex(f, id)
+                }
+            },
+            DhcpOption::DomainSearch(names) => write_list(f, names),
+            DhcpOption::ClientArchitecture(archs) => write_list(f, archs),
+            DhcpOption::UserClass(classes) => {
+                for (i, class) in classes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_text(f, class)?;
+                }
+                Ok(())
+            }
+            DhcpOption::ClientFqdn {
+                flags,
+                rcode1,
+                rcode2,
+                name,
+            } => write!(
+                f,
+                "{}, flags {:02x}, rcode {}/{}",
+                name, flags, rcode1, rcode2
+            ),
+            DhcpOption::VendorSpecificInformation(data) => write_text(f, data),
+            DhcpOption::RelayAgentInformation(subs) => {
+                let subs: Vec<_> = subs.iter().map(|s| s.to_raw()).collect();
+                write_sub_options(f, &subs)
+            }
+            DhcpOption::VendorIdentifyingVendorClass(classes) => {
+                for (i, class) in classes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "enterprise {}: ", class.enterprise)?;
+                    for (j, data) in class.data.iter().enumerate() {
+                        if j > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write_text(f, data)?;
+                    }
+                }
+                Ok(())
+            }
+            DhcpOption::VendorIdentifyingVendorSpecific(vendors) => {
+                for (i, vendor) in vendors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "enterprise {}: ", vendor.enterprise)?;
+                    write_sub_options(f, &vendor.options)?;
+                }
+                Ok(())
+            }
+            DhcpOption::Extension(ext) => write!(f, "{:?}", ext),
+            DhcpOption::Unrecognized(raw) => write_hex(f, &raw.data),
+        }
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Writes bytes as space separated hex.
+pub(crate) fn write_hex(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    for (i, b) in data.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+/// Writes bytes as a quoted string if they are printable ASCII, otherwise as hex.
+fn write_text(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    if data.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        write!(f, "\"{}\"", String::from_utf8_lossy(data))
+    } else {
+        write_hex(f, data)
+    }
+}
+
+fn write_sub_options(f: &mut fmt::Formatter, subs: &[RawDhcpOption]) -> fmt::Result {
+    for (i, sub) in subs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: ", sub.code)?;
+        write_text(f, &sub.data)?;
+    }
+    Ok(())
+}
+
+fn ipv4s_bytes(addrs: &[Ipv4Addr]) -> Vec<u8> {
+    addrs.iter().flat_map(|a| a.octets()).collect()
+}
+
+fn ipv4_pairs_bytes(pairs: &[(Ipv4Addr, Ipv4Addr)]) -> Vec<u8> {
+    pairs
+        .iter()
+        .flat_map(|(a, b)| IntoIterator::into_iter(a.octets()).chain(b.octets()))
+        .collect()
+}
+
+/// Encodes RFC 3925 enterprise blocks of already encoded items, each at most 255 bytes. An
+/// enterprise whose items exceed the 255 byte block limit is split into several blocks.
+fn enterprise_blocks_bytes(enterprises: impl Iterator<Item = (u32, Vec<Vec<u8>>)>) -> Vec<u8> {
+    let mut v = vec![];
+    for (enterprise, items) in enterprises {
+        let mut items = items.iter().peekable();
+        loop {
+            v.extend(&enterprise.to_be_bytes());
+            let len_at = v.len();
+            v.push(0);
+            while let Some(item) = items.peek() {
+                let len = v.len() - len_at - 1;
+                if len > 0 && len + item.len() > 255 {
+                    break;
+                }
+                v.extend(items.next().unwrap());
+            }
+            let len = v.len() - len_at - 1;
+            debug_assert!(len <= 255, "enterprise item exceeds block limit");
+            v[len_at] = len as u8;
+            if items.peek().is_none() {
+                break;
+            }
+        }
+    }
+    v
+}
+
+/// Encodes a name in uncompressed wire format, ending with the root label only if it is
+/// fully qualified.
+fn fqdn_name_bytes(name: &str) -> Vec<u8> {
+    let mut v = vec![];
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        v.push(label.len() as u8);
+        v.extend(label);
+    }
+    if name.ends_with('.') {
+        v.push(0);
+    }
+    v
+}
+
+/// Encodes names in RFC 1035 wire format, replacing any suffix already written with a
+/// pointer to it.
+fn domain_names_bytes(names: &[String]) -> Vec<u8> {
+    let mut v = vec![];
+    let mut suffixes = HashMap::new();
+    for name in names {
+        let labels: Vec<&str> = name.split('.').filter(|l| !l.is_empty()).collect();
+        let mut i = 0;
+        loop {
+            if i == labels.len() {
+                v.push(0);
+                break;
+            }
+            let suffix = labels[i..].join(".");
+            if let Some(&at) = suffixes.get(&suffix) {
+                v.extend(&(0xc000 | at as u16).to_be_bytes());
+                break;
+            }
+            if v.len() <= 0x3fff {
+                suffixes.insert(suffix, v.len());
+            }
+            let label = &labels[i].as_bytes()[..labels[i].len().min(63)];
+            v.push(label.len() as u8);
+            v.extend(label);
+            i += 1;
+        }
+    }
+    v
+}
+
+/// Encodes routes as RFC 3442 destination descriptors: the prefix length, the significant
+/// octets of the destination network, then the router.
+fn classless_routes_bytes(routes: &[(Ipv4Net, Ipv4Addr)]) -> Vec<u8> {
+    let mut v = vec![];
+    for (net, router) in routes {
+        let significant = (net.prefix_len() as usize).div_ceil(8);
+        v.push(net.prefix_len());
+        v.extend(&net.network().octets()[..significant]);
+        v.extend(router.octets().iter());
+    }
+    v
+}
+
 // DHCP Options;
 pub const SUBNET_MASK: u8 = 1;
 pub const TIME_OFFSET: u8 = 2;
@@ -172,9 +1493,24 @@ pub const STREETTALK_DIRECTORY_ASSISTANCE: u8 = 76;
 
 pub const RELAY_AGENT_INFORMATION: u8 = 82;
 
+// RELAY AGENT INFORMATION SUB-OPTIONS;
+pub const RELAY_AGENT_CIRCUIT_ID: u8 = 1;
+pub const RELAY_AGENT_REMOTE_ID: u8 = 2;
+pub const RELAY_AGENT_LINK_SELECTION: u8 = 5;
+pub const RELAY_AGENT_SUBSCRIBER_ID: u8 = 6;
+pub const RELAY_AGENT_FLAGS: u8 = 10;
+pub const RELAY_AGENT_SERVER_IDENTIFIER_OVERRIDE: u8 = 11;
+pub const RELAY_AGENT_VIRTUAL_SUBNET_SELECTION: u8 = 151;
+pub const RELAY_AGENT_VIRTUAL_SUBNET_SELECTION_CONTROL: u8 = 152;
+
+/// RELAY_AGENT_FLAGS bit set when the client's request reached the relay by unicast.
+pub const RELAY_AGENT_FLAG_UNICAST: u8 = 0x80;
+
 // DHCP EXTENSIONS
 pub const REQUESTED_IP_ADDRESS: u8 = 50;
 pub const IP_ADDRESS_LEASE_TIME: u8 = 51;
+/// IP_ADDRESS_LEASE_TIME of a lease that never expires, as held by BOOTP clients (RFC 1534)
+pub const INFINITE_LEASE_TIME: u32 = 0xffff_ffff;
 pub const OVERLOAD: u8 = 52;
 pub const DHCP_MESSAGE_TYPE: u8 = 53;
 pub const SERVER_IDENTIFIER: u8 = 54;
@@ -186,19 +1522,47 @@ pub const REBINDING_TIME_VALUE: u8 = 59;
 pub const VENDOR_CLASS_IDENTIFIER: u8 = 60;
 pub const CLIENT_IDENTIFIER: u8 = 61;
 
+/// CLIENT_IDENTIFIER type for an RFC 4361 IAID and DUID.
+pub const CLIENT_ID_RFC4361: u8 = 255;
+
+// DUID types;
+pub const DUID_LLT: u16 = 1;
+pub const DUID_EN: u16 = 2;
+pub const DUID_LL: u16 = 3;
+pub const DUID_UUID: u16 = 4;
+
 pub const TFTP_SERVER_NAME: u8 = 66;
 pub const BOOTFILE_NAME: u8 = 67;
 
 pub const USER_CLASS: u8 = 77;
 
+pub const CLIENT_FQDN: u8 = 81;
+
+// CLIENT FQDN FLAGS;
+/// Server should perform the A RR update.
+pub const FQDN_FLAG_S: u8 = 0x01;
+/// Server has overridden the client's S flag.
+pub const FQDN_FLAG_O: u8 = 0x02;
+/// Domain name is in canonical wire format.
+pub const FQDN_FLAG_E: u8 = 0x04;
+/// Server should perform no DNS updates.
+pub const FQDN_FLAG_N: u8 = 0x08;
+
 pub const CLIENT_ARCHITECTURE: u8 = 93;
 
 pub const TZ_POSIX_STRING: u8 = 100;
 pub const TZ_DATABASE_STRING: u8 = 101;
 
+pub const DOMAIN_SEARCH: u8 = 119;
+
 pub const CLASSLESS_ROUTE_FORMAT: u8 = 121;
 
-/// Returns title of DHCP Option code, if known.
+pub const V_I_VENDOR_CLASS: u8 = 124;
+pub const V_I_VENDOR_SPECIFIC_INFORMATION: u8 = 125;
+
+pub const MS_CLASSLESS_ROUTE_FORMAT: u8 = 249;
+
+/// Returns title of DHCP Option code, if known, including registered extensions.
 pub fn title(code: u8) -> Option<&'static str> {
     Some(match code {
         SUBNET_MASK => "Subnet Mask",
@@ -297,13 +1661,22 @@ pub fn title(code: u8) -> Option<&'static str> {
 
         USER_CLASS => "User Class",
 
+        CLIENT_FQDN => "Client FQDN",
+
         CLIENT_ARCHITECTURE => "Client Architecture",
 
         TZ_POSIX_STRING => "TZ-POSIX String",
         TZ_DATABASE_STRING => "TZ-Database String",
+        DOMAIN_SEARCH => "Domain Search",
+
         CLASSLESS_ROUTE_FORMAT => "Classless Route Format",
 
-        _ => return None,
+        V_I_VENDOR_CLASS => "V-I Vendor Class",
+        V_I_VENDOR_SPECIFIC_INFORMATION => "V-I Vendor-Specific Information",
+
+        MS_CLASSLESS_ROUTE_FORMAT => "Microsoft Classless Route Format",
+
+        _ => return registered_title(code),
     })
 }
 
@@ -319,6 +1692,7 @@ pub fn title(code: u8) -> Option<&'static str> {
 /// > and its length is 1.
 ///
 #[derive(Primitive, Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MessageType {
     /// Client broadcast to locate available servers.
     Discover = 1,
@@ -350,8 +1724,103 @@ pub enum MessageType {
     Inform = 8,
 }
 
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MessageType::Discover => "DHCPDISCOVER",
+            MessageType::Offer => "DHCPOFFER",
+            MessageType::Request => "DHCPREQUEST",
+            MessageType::Decline => "DHCPDECLINE",
+            MessageType::Ack => "DHCPACK",
+            MessageType::Nak => "DHCPNAK",
+            MessageType::Release => "DHCPRELEASE",
+            MessageType::Inform => "DHCPINFORM",
+        })
+    }
+}
+
 impl MessageType {
     pub fn from(val: u8) -> Result<MessageType, String> {
         MessageType::from_u8(val).ok_or_else(|| format!["Invalid DHCP Message Type: {:?}", val])
     }
 }
+
+/// Client system architecture type (RFC 4578 section 2.1, and the IANA registry that
+/// extends it).
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Architecture {
+    IntelX86Pc,
+    NecPc98,
+    EfiItanium,
+    DecAlpha,
+    ArcX86,
+    IntelLeanClient,
+    EfiIa32,
+    EfiBc,
+    EfiXscale,
+    EfiX64,
+    EfiArm32,
+    EfiArm64,
+    Other(u16),
+}
+
+impl From<u16> for Architecture {
+    fn from(val: u16) -> Architecture {
+        match val {
+            0 => Architecture::IntelX86Pc,
+            1 => Architecture::NecPc98,
+            2 => Architecture::EfiItanium,
+            3 => Architecture::DecAlpha,
+            4 => Architecture::ArcX86,
+            5 => Architecture::IntelLeanClient,
+            6 => Architecture::EfiIa32,
+            7 => Architecture::EfiBc,
+            8 => Architecture::EfiXscale,
+            9 => Architecture::EfiX64,
+            10 => Architecture::EfiArm32,
+            11 => Architecture::EfiArm64,
+            other => Architecture::Other(other),
+        }
+    }
+}
+
+impl From<Architecture> for u16 {
+    fn from(arch: Architecture) -> u16 {
+        match arch {
+            Architecture::IntelX86Pc => 0,
+            Architecture::NecPc98 => 1,
+            Architecture::EfiItanium => 2,
+            Architecture::DecAlpha => 3,
+            Architecture::ArcX86 => 4,
+            Architecture::IntelLeanClient => 5,
+            Architecture::EfiIa32 => 6,
+            Architecture::EfiBc => 7,
+            Architecture::EfiXscale => 8,
+            Architecture::EfiX64 => 9,
+            Architecture::EfiArm32 => 10,
+            Architecture::EfiArm64 => 11,
+            Architecture::Other(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Architecture::IntelX86Pc => f.write_str("Intel x86PC"),
+            Architecture::NecPc98 => f.write_str("NEC/PC98"),
+            Architecture::EfiItanium => f.write_str("EFI Itanium"),
+            Architecture::DecAlpha => f.write_str("DEC Alpha"),
+            Architecture::ArcX86 => f.write_str("Arc x86"),
+            Architecture::IntelLeanClient => f.write_str("Intel Lean Client"),
+            Architecture::EfiIa32 => f.write_str("EFI IA32"),
+            Architecture::EfiBc => f.write_str("EFI BC"),
+            Architecture::EfiXscale => f.write_str("EFI Xscale"),
+            Architecture::EfiX64 => f.write_str("EFI x86-64"),
+            Architecture::EfiArm32 => f.write_str("ARM 32-bit UEFI"),
+            Architecture::EfiArm64 => f.write_str("ARM 64-bit UEFI"),
+            Architecture::Other(other) => write!(f, "{}", other),
+        }
+    }
+}
@@ -1,105 +1,847 @@
 use crate::options::*;
 
 use nom::bytes::complete::{tag, take};
-use nom::multi::{many0, many_till};
-use nom::number::complete::{be_u16, be_u32, be_u8};
+use nom::multi::many0;
+use nom::number::complete::{be_i32, be_u16, be_u32, be_u8};
+use nom::sequence::pair;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
 use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub enum Err<I> {
-    NomError(nom::Err<(I, nom::error::ErrorKind)>),
+/// Error decoding a DHCP packet or option.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    /// Byte offset from the start of the input at which the problem was found.
+    pub offset: usize,
+    /// Code of the option being decoded, if any.
+    pub option: Option<u8>,
+    pub kind: DecodeErrorKind,
+}
+
+/// Reasons a DHCP packet or option cannot be decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeErrorKind {
+    /// Input ended before the field or option was complete.
+    Truncated,
+    /// op is neither BOOTREQUEST nor BOOTREPLY.
+    InvalidOpCode(u8),
+    /// Hardware address length exceeds the 16 byte chaddr field.
+    InvalidHlen(u8),
+    /// END found where an option was expected.
+    UnexpectedEnd,
+    /// Options are not terminated by END.
+    MissingEnd,
+    /// sname or file is not NUL terminated.
+    UnterminatedString,
     NonUtf8String,
-    UnrecognizedMessageType,
-    InvalidHlen,
+    UnrecognizedMessageType(u8),
+    /// Overload option has a value other than 1, 2 or 3.
+    InvalidOverload,
+    /// Option data has the wrong length for its type.
+    InvalidLength(usize),
+    /// Classless route prefix length is over 32 or its destination has host bits set.
+    InvalidPrefix(u8),
+    /// Domain name label or compression pointer is malformed, out of bounds or loops.
+    InvalidDomainName,
+    /// Any other parser failure.
+    Nom(nom::error::ErrorKind),
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeErrorKind::Truncated => write!(f, "truncated"),
+            DecodeErrorKind::InvalidOpCode(op) => write!(f, "invalid op code {}", op),
+            DecodeErrorKind::InvalidHlen(hlen) => {
+                write!(f, "invalid hardware address length {}", hlen)
+            }
+            DecodeErrorKind::UnexpectedEnd => write!(f, "unexpected end option"),
+            DecodeErrorKind::MissingEnd => write!(f, "missing end option"),
+            DecodeErrorKind::UnterminatedString => write!(f, "string is not NUL terminated"),
+            DecodeErrorKind::NonUtf8String => write!(f, "string is not valid UTF-8"),
+            DecodeErrorKind::UnrecognizedMessageType(t) => {
+                write!(f, "unrecognized message type {}", t)
+            }
+            DecodeErrorKind::InvalidOverload => write!(f, "invalid overload value"),
+            DecodeErrorKind::InvalidLength(len) => write!(f, "invalid option length {}", len),
+            DecodeErrorKind::InvalidPrefix(len) => write!(f, "invalid route prefix /{}", len),
+            DecodeErrorKind::InvalidDomainName => write!(f, "invalid domain name"),
+            DecodeErrorKind::Nom(kind) => write!(f, "parser error ({})", kind.description()),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)?;
+        match self.option {
+            Some(code) => match title(code) {
+                Some(title) => write!(f, " in option {} ({})", code, title),
+                None => write!(f, " in option {}", code),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Parser error, located by the input remaining where it occurred.
+struct Error<'a> {
+    input: &'a [u8],
+    option: Option<u8>,
+    kind: DecodeErrorKind,
+}
+
+impl<'a> Error<'a> {
+    fn new(input: &'a [u8], kind: DecodeErrorKind) -> nom::Err<Self> {
+        nom::Err::Error(Error {
+            input,
+            option: None,
+            kind,
+        })
+    }
+
+    fn fail<O>(input: &'a [u8], kind: DecodeErrorKind) -> IResult<'a, O> {
+        Err(Error::new(input, kind))
+    }
+
+    /// Converts to a DecodeError with offset relative to start.
+    fn locate(self, start: &[u8]) -> DecodeError {
+        let offset = (self.input.as_ptr() as usize).wrapping_sub(start.as_ptr() as usize);
+        DecodeError {
+            offset: offset.min(start.len()),
+            option: self.option,
+            kind: self.kind,
+        }
+    }
 }
 
-impl<I> nom::error::ParseError<I> for Err<I> {
-    fn from_error_kind(input: I, kind: nom::error::ErrorKind) -> Self {
-        Err::NomError(nom::Err::Error((input, kind)))
+impl<'a> nom::error::ParseError<&'a [u8]> for Error<'a> {
+    fn from_error_kind(input: &'a [u8], kind: nom::error::ErrorKind) -> Self {
+        Error {
+            input,
+            option: None,
+            kind: match kind {
+                nom::error::ErrorKind::Eof => DecodeErrorKind::Truncated,
+                kind => DecodeErrorKind::Nom(kind),
+            },
+        }
     }
 
-    fn append(_input: I, _kind: nom::error::ErrorKind, other: Self) -> Self {
+    fn append(_input: &'a [u8], _kind: nom::error::ErrorKind, other: Self) -> Self {
         other
     }
 }
 
-type IResult<I, O> = nom::IResult<I, O, Err<I>>;
+type IResult<'a, O> = nom::IResult<&'a [u8], O, Error<'a>>;
+
+/// Applies f to the error within a nom::Err.
+fn map_error<'a, 'b>(
+    e: nom::Err<Error<'a>>,
+    f: impl FnOnce(Error<'a>) -> Error<'b>,
+) -> nom::Err<Error<'b>> {
+    match e {
+        nom::Err::Incomplete(n) => nom::Err::Incomplete(n),
+        nom::Err::Error(e) => nom::Err::Error(f(e)),
+        nom::Err::Failure(e) => nom::Err::Failure(f(e)),
+    }
+}
+
+/// Attributes errors to the option with the given code.
+fn in_option(code: u8) -> impl for<'a> Fn(nom::Err<Error<'a>>) -> nom::Err<Error<'a>> {
+    move |e| {
+        map_error(e, |e| Error {
+            option: Some(code),
+            ..e
+        })
+    }
+}
+
+/// Converts a parser failure into a DecodeError with offset relative to start.
+fn to_decode_error(e: nom::Err<Error>, start: &[u8]) -> DecodeError {
+    match e {
+        nom::Err::Incomplete(_) => DecodeError {
+            offset: start.len(),
+            option: None,
+            kind: DecodeErrorKind::Truncated,
+        },
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.locate(start),
+    }
+}
+
+/// Client hardware address, made up of its ARP hardware type (htype) and up to 16 bytes of
+/// address (chaddr).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct HardwareAddress {
+    htype: u8,
+    len: u8,
+    addr: [u8; CHADDR_LEN],
+}
+
+impl HardwareAddress {
+    /// Returns None if addr is longer than 16 bytes.
+    pub fn new(htype: u8, addr: &[u8]) -> Option<HardwareAddress> {
+        if addr.len() > CHADDR_LEN {
+            return None;
+        }
+        let mut a = [0; CHADDR_LEN];
+        a[..addr.len()].clone_from_slice(addr);
+        Some(HardwareAddress {
+            htype,
+            len: addr.len() as u8,
+            addr: a,
+        })
+    }
+
+    /// Ethernet MAC address.
+    pub fn ethernet(mac: [u8; 6]) -> HardwareAddress {
+        HardwareAddress::new(HTYPE_ETHERNET, &mac).unwrap()
+    }
+
+    pub fn htype(&self) -> u8 {
+        self.htype
+    }
+
+    /// Significant bytes of the address (hlen long).
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.addr[..self.len as usize]
+    }
+}
+
+impl From<[u8; 6]> for HardwareAddress {
+    fn from(mac: [u8; 6]) -> HardwareAddress {
+        HardwareAddress::ethernet(mac)
+    }
+}
+
+/// Formats address as hex pairs separated by colons.
+impl fmt::Display for HardwareAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, b) in self.as_bytes().iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// BOOTP flags field (RFC 1542). Only the high bit, BROADCAST, is defined; the remaining
+/// reserved bits are kept as received so they survive re-encoding.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flags(pub u16);
+
+impl Flags {
+    pub const BROADCAST: u16 = 0x8000;
+
+    /// Client requests replies be broadcast, as it cannot receive unicast before configuration.
+    pub fn broadcast(self) -> bool {
+        self.0 & Flags::BROADCAST != 0
+    }
+
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        if broadcast {
+            self.0 |= Flags::BROADCAST;
+        } else {
+            self.0 &= !Flags::BROADCAST;
+        }
+    }
+
+    /// Reserved bits, which clients must set to zero.
+    pub fn reserved(self) -> u16 {
+        self.0 & !Flags::BROADCAST
+    }
+}
 
 /// DHCP Packet Structure
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Packet {
     pub reply: bool, // false = request, true = reply
     pub hops: u8,
     pub xid: u32, // Random identifier
     pub secs: u16,
-    pub broadcast: bool,
+    pub flags: Flags,
     pub ciaddr: Ipv4Addr,
     pub yiaddr: Ipv4Addr,
     pub siaddr: Ipv4Addr,
     pub giaddr: Ipv4Addr,
-    pub chaddr: [u8; 6],
+    pub chaddr: HardwareAddress,
+    pub sname: Option<String>, // Optional server host name
+    pub file: Option<String>,  // Boot file name
     pub options: Vec<DhcpOption>,
+    /// BOOTP vendor area (RFC 951) of a packet without the DHCP magic cookie, in which case
+    /// there are no options. BOOTP packets with RFC 1048 vendor extensions have options instead.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub vend: Option<Vec<u8>>,
 }
 
-fn decode_reply(input: &[u8]) -> IResult<&[u8], bool> {
-    let (input, reply) = take(1u8)(input)?;
-    Ok((
-        input,
-        match reply[0] {
-            BOOT_REPLY => true,
-            BOOT_REQUEST => false,
-            _ => {
-                // @TODO: Throw an error
-                false
+fn decode_reply(input: &[u8]) -> IResult<'_, bool> {
+    let (rest, reply) = be_u8(input)?;
+    match reply {
+        BOOT_REPLY => Ok((rest, true)),
+        BOOT_REQUEST => Ok((rest, false)),
+        op => Error::fail(input, DecodeErrorKind::InvalidOpCode(op)),
+    }
+}
+
+/// Decodes a NUL terminated string from a fixed size header field (sname or file).
+/// Returns None if the field is empty. If lenient, a field filling its whole size is
+/// accepted without NUL and invalid UTF-8 is replaced.
+fn decode_cstr(field: &[u8], lenient: bool) -> IResult<'_, Option<String>> {
+    let len = match field.iter().position(|&b| b == 0) {
+        Some(len) => len,
+        None if lenient => field.len(),
+        None => return Error::fail(field, DecodeErrorKind::UnterminatedString),
+    };
+    if len == 0 {
+        return Ok((field, None));
+    }
+    match std::str::from_utf8(&field[..len]) {
+        Ok(s) => Ok((field, Some(s.to_string()))),
+        Err(_) if lenient => Ok((field, Some(String::from_utf8_lossy(&field[..len]).into()))),
+        Err(_) => Error::fail(field, DecodeErrorKind::NonUtf8String),
+    }
+}
+
+fn decode_string(data: &[u8]) -> Result<String, nom::Err<Error<'_>>> {
+    match std::str::from_utf8(data) {
+        Ok(s) => Ok(s.to_string()),
+        Err(_) => Err(Error::new(data, DecodeErrorKind::NonUtf8String)),
+    }
+}
+
+fn decode_ipv4(p: &[u8]) -> IResult<'_, Ipv4Addr> {
+    let (input, addr) = take(4u8)(p)?;
+    Ok((input, Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3])))
+}
+
+/// Decodes a list of RFC 1035 names, following compression pointers within data.
+fn decode_domain_names(data: &[u8]) -> Result<Vec<String>, nom::Err<Error<'_>>> {
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (name, next) = decode_domain_name(data, pos)?;
+        names.push(name);
+        pos = next;
+    }
+    Ok(names)
+}
+
+/// Decodes the name starting at data[start], returning it and the position after it.
+/// Each pointer must point before the previous one (or before start), so pointers cannot loop.
+fn decode_domain_name(data: &[u8], start: usize) -> Result<(String, usize), nom::Err<Error<'_>>> {
+    let invalid = |at: usize| {
+        Error::new(
+            &data[at.min(data.len())..],
+            DecodeErrorKind::InvalidDomainName,
+        )
+    };
+    let mut name = String::new();
+    let mut at = start;
+    let mut limit = start;
+    let mut next = None;
+    loop {
+        let len = *data.get(at).ok_or_else(|| invalid(at))? as usize;
+        match len & 0xc0 {
+            0 if len == 0 => break,
+            0 => {
+                let label = data.get(at + 1..at + 1 + len).ok_or_else(|| invalid(at))?;
+                let label = std::str::from_utf8(label)
+                    .map_err(|_| Error::new(label, DecodeErrorKind::NonUtf8String))?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(label);
+                if name.len() > MAX_DOMAIN_NAME_LEN {
+                    return Err(invalid(at));
+                }
+                at += 1 + len;
+            }
+            0xc0 => {
+                let low = *data.get(at + 1).ok_or_else(|| invalid(at))? as usize;
+                let target = (len & 0x3f) << 8 | low;
+                if target >= limit {
+                    return Err(invalid(at));
+                }
+                next.get_or_insert(at + 2);
+                limit = target;
+                at = target;
+            }
+            _ => return Err(invalid(at)),
+        }
+    }
+    Ok((name, next.unwrap_or(at + 1)))
+}
+
+/// Decodes an uncompressed wire format name, adding a trailing dot if it ends with the
+/// root label, as in RFC 4702 partial names.
+fn decode_fqdn_name(data: &[u8]) -> Result<String, nom::Err<Error<'_>>> {
+    let mut name = String::new();
+    let mut at = 0;
+    while at < data.len() {
+        let len = data[at] as usize;
+        if len == 0 && at + 1 == data.len() {
+            name.push('.');
+            break;
+        }
+        let label = match data.get(at + 1..at + 1 + len) {
+            Some(label) if len > 0 && len <= 63 => label,
+            _ => return Err(Error::new(&data[at..], DecodeErrorKind::InvalidDomainName)),
+        };
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(&decode_string(label)?);
+        at += 1 + len;
+    }
+    if name.len() > MAX_DOMAIN_NAME_LEN + 1 {
+        return Err(Error::new(data, DecodeErrorKind::InvalidDomainName));
+    }
+    Ok(name)
+}
+
+/// Decodes RFC 3442 destination descriptors and routers.
+fn decode_classless_routes(mut input: &[u8]) -> IResult<'_, Vec<(Ipv4Net, Ipv4Addr)>> {
+    let mut routes = Vec::new();
+    while !input.is_empty() {
+        let (rest, prefix_len) = be_u8(input)?;
+        if prefix_len > 32 {
+            return Error::fail(input, DecodeErrorKind::InvalidPrefix(prefix_len));
+        }
+        let (rest, significant) = take((prefix_len as usize).div_ceil(8))(rest)?;
+        let mut octets = [0; 4];
+        octets[..significant.len()].copy_from_slice(significant);
+        let net = match Ipv4Net::new(Ipv4Addr::from(octets), prefix_len) {
+            Ok(net) if net.addr() == net.network() => net,
+            _ => return Error::fail(input, DecodeErrorKind::InvalidPrefix(prefix_len)),
+        };
+        let (rest, router) = decode_ipv4(rest)?;
+        routes.push((net, router));
+        input = rest;
+    }
+    Ok((input, routes))
+}
+
+/// Decodes the sub-options of RELAY_AGENT_INFORMATION.
+fn decode_relay_agent_information(mut input: &[u8]) -> IResult<'_, Vec<RelayAgentSubOption>> {
+    let mut subs = Vec::new();
+    while !input.is_empty() {
+        let (rest, code) = be_u8(input)?;
+        let (rest, len) = be_u8(rest)?;
+        let (rest, data) = take(len)(rest)?;
+        subs.push(match code {
+            RELAY_AGENT_CIRCUIT_ID => RelayAgentSubOption::CircuitId(data.to_vec()),
+            RELAY_AGENT_REMOTE_ID => RelayAgentSubOption::RemoteId(data.to_vec()),
+            RELAY_AGENT_LINK_SELECTION => {
+                RelayAgentSubOption::LinkSelection(decode_all(data, decode_ipv4)?)
+            }
+            RELAY_AGENT_SUBSCRIBER_ID => RelayAgentSubOption::SubscriberId(decode_string(data)?),
+            RELAY_AGENT_FLAGS => RelayAgentSubOption::RelayAgentFlags(decode_all(data, be_u8)?),
+            RELAY_AGENT_SERVER_IDENTIFIER_OVERRIDE => {
+                RelayAgentSubOption::ServerIdentifierOverride(decode_all(data, decode_ipv4)?)
             }
+            RELAY_AGENT_VIRTUAL_SUBNET_SELECTION => match data.split_first() {
+                Some((&vss_type, info)) => RelayAgentSubOption::VirtualSubnetSelection {
+                    vss_type,
+                    info: info.to_vec(),
+                },
+                None => return Error::fail(data, DecodeErrorKind::InvalidLength(0)),
+            },
+            RELAY_AGENT_VIRTUAL_SUBNET_SELECTION_CONTROL if data.is_empty() => {
+                RelayAgentSubOption::VirtualSubnetSelectionControl
+            }
+            RELAY_AGENT_VIRTUAL_SUBNET_SELECTION_CONTROL => {
+                return Error::fail(data, DecodeErrorKind::InvalidLength(data.len()))
+            }
+            _ => RelayAgentSubOption::Unrecognized(RawDhcpOption {
+                code,
+                data: data.to_vec(),
+            }),
+        });
+        input = rest;
+    }
+    Ok((input, subs))
+}
+
+/// Decodes RFC 3925 enterprise blocks, parsing each block's data with parser.
+/// Blocks with the same enterprise number are merged.
+fn decode_enterprise_blocks<'a, O>(
+    mut input: &'a [u8],
+    mut parser: impl FnMut(&'a [u8]) -> IResult<'a, O>,
+) -> IResult<'a, Vec<(u32, Vec<O>)>> {
+    let mut blocks: Vec<(u32, Vec<O>)> = Vec::new();
+    while !input.is_empty() {
+        let (rest, enterprise) = be_u32(input)?;
+        let (rest, len) = be_u8(rest)?;
+        let (rest, mut data) = take(len)(rest)?;
+        let at = match blocks.iter().position(|(e, _)| *e == enterprise) {
+            Some(at) => at,
+            None => {
+                blocks.push((enterprise, Vec::new()));
+                blocks.len() - 1
+            }
+        };
+        while !data.is_empty() {
+            let (rest, item) = parser(data)?;
+            blocks[at].1.push(item);
+            data = rest;
+        }
+        input = rest;
+    }
+    Ok((input, blocks))
+}
+
+fn decode_vendor_class_data(input: &[u8]) -> IResult<'_, Vec<u8>> {
+    let (rest, len) = be_u8(input)?;
+    let (rest, data) = take(len)(rest)?;
+    Ok((rest, data.to_vec()))
+}
+
+fn decode_vendor_sub_option(input: &[u8]) -> IResult<'_, RawDhcpOption> {
+    let (rest, code) = be_u8(input)?;
+    let (rest, len) = be_u8(rest)?;
+    let (rest, data) = take(len)(rest)?;
+    Ok((
+        rest,
+        RawDhcpOption {
+            code,
+            data: data.to_vec(),
         },
     ))
 }
 
-fn decode_ipv4(p: &[u8]) -> IResult<&[u8], Ipv4Addr> {
-    let (input, addr) = take(4u8)(p)?;
-    Ok((input, Ipv4Addr::new(addr[0], addr[1], addr[2], addr[3])))
+/// Decodes RFC 3004 user classes, falling back to a single class if data is not a valid
+/// list of non-empty classes.
+fn decode_user_classes(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut classes = Vec::new();
+    let mut rest = data;
+    while let Some((&len, tail)) = rest.split_first() {
+        if len == 0 || tail.len() < len as usize {
+            return vec![data.to_vec()];
+        }
+        classes.push(tail[..len as usize].to_vec());
+        rest = &tail[len as usize..];
+    }
+    classes
+}
+
+/// Applies parser to the whole of an option's data, failing if any bytes are left over.
+fn decode_all<'a, O>(
+    data: &'a [u8],
+    mut parser: impl FnMut(&'a [u8]) -> IResult<'a, O>,
+) -> Result<O, nom::Err<Error<'a>>> {
+    match parser(data) {
+        Ok((&[], value)) => Ok(value),
+        Ok(_)
+        | Err(nom::Err::Error(Error {
+            kind: DecodeErrorKind::Truncated,
+            ..
+        })) => Err(Error::new(data, DecodeErrorKind::InvalidLength(data.len()))),
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a single option's code and data, without interpreting the data.
+fn decode_raw_option(input: &[u8]) -> IResult<'_, (u8, &[u8])> {
+    let (rest, code) = be_u8(input)?;
+    if code == END {
+        return Error::fail(input, DecodeErrorKind::UnexpectedEnd);
+    }
+    let (rest, len) = be_u8(rest).map_err(in_option(code))?;
+    let (rest, data) = take(len)(rest).map_err(in_option(code))?;
+    Ok((rest, (code, data)))
+}
+
+/// Parses a single option, with error offsets relative to input.
+pub fn decode_option(input: &[u8]) -> Result<(&[u8], DhcpOption), DecodeError> {
+    let (rest, (code, data)) = decode_raw_option(input).map_err(|e| to_decode_error(e, input))?;
+    match decode_option_data(code, data) {
+        Ok(option) => Ok((rest, option)),
+        Err(e) => Err(to_decode_error(in_option(code)(e), input)),
+    }
+}
+
+/// Interprets option data according to its code, using a registered extension for codes
+/// without a built-in decoder.
+fn decode_option_data(code: u8, data: &[u8]) -> Result<DhcpOption, nom::Err<Error<'_>>> {
+    match decode_builtin(code, data)? {
+        DhcpOption::Unrecognized(raw) => match decode_registered(code, data) {
+            Some(ext) => ext
+                .map(DhcpOption::Extension)
+                .map_err(|kind| Error::new(data, kind)),
+            None => Ok(DhcpOption::Unrecognized(raw)),
+        },
+        option => Ok(option),
+    }
 }
 
-pub fn decode_option(input: &[u8]) -> IResult<&[u8], DhcpOption> {
-    let (input, code) = be_u8(input)?;
-    assert!(code != END);
+/// Whether code is PAD, END or has a built-in decoder, which extensions cannot replace.
+pub(crate) fn has_builtin_decoder(code: u8) -> bool {
+    code == PAD
+        || code == END
+        || !matches!(decode_builtin(code, &[]), Ok(DhcpOption::Unrecognized(_)))
+}
 
-    let (input, len) = be_u8(input)?;
-    let (input, data) = take(len)(input)?;
+/// Interprets option data with the built-in decoder for its code, if any, otherwise as
+/// Unrecognized.
+fn decode_builtin(code: u8, data: &[u8]) -> Result<DhcpOption, nom::Err<Error<'_>>> {
     let option = match code {
-        DHCP_MESSAGE_TYPE => DhcpOption::DhcpMessageType(match MessageType::from(be_u8(data)?.1) {
-            Ok(x) => x,
-            Err(_) => return Err(nom::Err::Error(Err::UnrecognizedMessageType)),
-        }),
-        SERVER_IDENTIFIER => DhcpOption::ServerIdentifier(decode_ipv4(data)?.1),
+        SUBNET_MASK => DhcpOption::SubnetMask(decode_all(data, decode_ipv4)?),
+        TIME_OFFSET => DhcpOption::TimeOffset(decode_all(data, be_i32)?),
+        ROUTER => DhcpOption::Router(decode_all(data, many0(decode_ipv4))?),
+        TIME_SERVER => DhcpOption::TimeServer(decode_all(data, many0(decode_ipv4))?),
+        NAME_SERVER => DhcpOption::NameServer(decode_all(data, many0(decode_ipv4))?),
+        DOMAIN_NAME_SERVER => DhcpOption::DomainNameServer(decode_all(data, many0(decode_ipv4))?),
+        LOG_SERVER => DhcpOption::LogServer(decode_all(data, many0(decode_ipv4))?),
+        COOKIE_SERVER => DhcpOption::CookieServer(decode_all(data, many0(decode_ipv4))?),
+        LPR_SERVER => DhcpOption::LprServer(decode_all(data, many0(decode_ipv4))?),
+        IMPRESS_SERVER => DhcpOption::ImpressServer(decode_all(data, many0(decode_ipv4))?),
+        RESOURCE_LOCATION_SERVER => {
+            DhcpOption::ResourceLocationServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        HOST_NAME => DhcpOption::HostName(decode_string(data)?),
+        BOOT_FILE_SIZE => DhcpOption::BootFileSize(decode_all(data, be_u16)?),
+        MERIT_DUMP_FILE => DhcpOption::MeritDumpFile(decode_string(data)?),
+        DOMAIN_NAME => DhcpOption::DomainName(decode_string(data)?),
+        SWAP_SERVER => DhcpOption::SwapServer(decode_all(data, decode_ipv4)?),
+        ROOT_PATH => DhcpOption::RootPath(decode_string(data)?),
+        EXTENSIONS_PATH => DhcpOption::ExtensionsPath(decode_string(data)?),
+        IP_FORWARDING_ENABLE_DISABLE => {
+            DhcpOption::IpForwardingEnableDisable(decode_all(data, be_u8)? != 0)
+        }
+        NON_LOCAL_SOURCE_ROUTING_ENABLE_DISABLE => {
+            DhcpOption::NonLocalSourceRoutingEnableDisable(decode_all(data, be_u8)? != 0)
+        }
+        POLICY_FILTER => {
+            DhcpOption::PolicyFilter(decode_all(data, many0(pair(decode_ipv4, decode_ipv4)))?)
+        }
+        MAXIMUM_DATAGRAM_REASSEMBLY_SIZE => {
+            DhcpOption::MaximumDatagramReassemblySize(decode_all(data, be_u16)?)
+        }
+        DEFAULT_IP_TIME_TO_LIVE => DhcpOption::DefaultIpTimeToLive(decode_all(data, be_u8)?),
+        PATH_MTU_AGING_TIMEOUT => DhcpOption::PathMtuAgingTimeout(decode_all(data, be_u32)?),
+        PATH_MTU_PLATEAU_TABLE => DhcpOption::PathMtuPlateauTable(decode_all(data, many0(be_u16))?),
+        INTERFACE_MTU => DhcpOption::InterfaceMtu(decode_all(data, be_u16)?),
+        ALL_SUBNETS_ARE_LOCAL => DhcpOption::AllSubnetsAreLocal(decode_all(data, be_u8)? != 0),
+        BROADCAST_ADDRESS => DhcpOption::BroadcastAddress(decode_all(data, decode_ipv4)?),
+        PERFORM_MASK_DISCOVERY => DhcpOption::PerformMaskDiscovery(decode_all(data, be_u8)? != 0),
+        MASK_SUPPLIER => DhcpOption::MaskSupplier(decode_all(data, be_u8)? != 0),
+        PERFORM_ROUTER_DISCOVERY => {
+            DhcpOption::PerformRouterDiscovery(decode_all(data, be_u8)? != 0)
+        }
+        ROUTER_SOLICITATION_ADDRESS => {
+            DhcpOption::RouterSolicitationAddress(decode_all(data, decode_ipv4)?)
+        }
+        STATIC_ROUTE => {
+            DhcpOption::StaticRoute(decode_all(data, many0(pair(decode_ipv4, decode_ipv4)))?)
+        }
+        TRAILER_ENCAPSULATION => DhcpOption::TrailerEncapsulation(decode_all(data, be_u8)? != 0),
+        ARP_CACHE_TIMEOUT => DhcpOption::ArpCacheTimeout(decode_all(data, be_u32)?),
+        ETHERNET_ENCAPSULATION => DhcpOption::EthernetEncapsulation(decode_all(data, be_u8)? != 0),
+        TCP_DEFAULT_TTL => DhcpOption::TcpDefaultTtl(decode_all(data, be_u8)?),
+        TCP_KEEPALIVE_INTERVAL => DhcpOption::TcpKeepaliveInterval(decode_all(data, be_u32)?),
+        TCP_KEEPALIVE_GARBAGE => DhcpOption::TcpKeepaliveGarbage(decode_all(data, be_u8)? != 0),
+        NETWORK_INFORMATION_SERVICE_DOMAIN => {
+            DhcpOption::NetworkInformationServiceDomain(decode_string(data)?)
+        }
+        NETWORK_INFORMATION_SERVERS => {
+            DhcpOption::NetworkInformationServers(decode_all(data, many0(decode_ipv4))?)
+        }
+        NETWORK_TIME_PROTOCOL_SERVERS => {
+            DhcpOption::NetworkTimeProtocolServers(decode_all(data, many0(decode_ipv4))?)
+        }
+        NETBIOS_OVER_TCPIP_NAME_SERVER => {
+            DhcpOption::NetbiosOverTcpipNameServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        NETBIOS_OVER_TCPIP_DATAGRAM_DISTRIBUTION_SERVER => {
+            DhcpOption::NetbiosOverTcpipDatagramDistributionServer(decode_all(
+                data,
+                many0(decode_ipv4),
+            )?)
+        }
+        NETBIOS_OVER_TCPIP_NODE_TYPE => {
+            DhcpOption::NetbiosOverTcpipNodeType(decode_all(data, be_u8)?)
+        }
+        NETBIOS_OVER_TCPIP_SCOPE => DhcpOption::NetbiosOverTcpipScope(decode_string(data)?),
+        XWINDOW_SYSTEM_FONT_SERVER => {
+            DhcpOption::XwindowSystemFontServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        XWINDOW_SYSTEM_DISPLAY_MANAGER => {
+            DhcpOption::XwindowSystemDisplayManager(decode_all(data, many0(decode_ipv4))?)
+        }
+        NETWORK_INFORMATION_SERVICEPLUS_DOMAIN => {
+            DhcpOption::NetworkInformationServiceplusDomain(decode_string(data)?)
+        }
+        NETWORK_INFORMATION_SERVICEPLUS_SERVERS => {
+            DhcpOption::NetworkInformationServiceplusServers(decode_all(data, many0(decode_ipv4))?)
+        }
+        MOBILE_IP_HOME_AGENT => {
+            DhcpOption::MobileIpHomeAgent(decode_all(data, many0(decode_ipv4))?)
+        }
+        SIMPLE_MAIL_TRANSPORT_PROTOCOL => {
+            DhcpOption::SimpleMailTransportProtocol(decode_all(data, many0(decode_ipv4))?)
+        }
+        POST_OFFICE_PROTOCOL_SERVER => {
+            DhcpOption::PostOfficeProtocolServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        NETWORK_NEWS_TRANSPORT_PROTOCOL => {
+            DhcpOption::NetworkNewsTransportProtocol(decode_all(data, many0(decode_ipv4))?)
+        }
+        DEFAULT_WORLD_WIDE_WEB_SERVER => {
+            DhcpOption::DefaultWorldWideWebServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        DEFAULT_FINGER_SERVER => {
+            DhcpOption::DefaultFingerServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        DEFAULT_INTERNET_RELAY_CHAT_SERVER => {
+            DhcpOption::DefaultInternetRelayChatServer(decode_all(data, many0(decode_ipv4))?)
+        }
+        STREETTALK_SERVER => DhcpOption::StreettalkServer(decode_all(data, many0(decode_ipv4))?),
+        STREETTALK_DIRECTORY_ASSISTANCE => {
+            DhcpOption::StreettalkDirectoryAssistance(decode_all(data, many0(decode_ipv4))?)
+        }
+        REQUESTED_IP_ADDRESS => DhcpOption::RequestedIpAddress(decode_all(data, decode_ipv4)?),
+        IP_ADDRESS_LEASE_TIME => DhcpOption::IpAddressLeaseTime(decode_all(data, be_u32)?),
+        DHCP_MESSAGE_TYPE => {
+            let t = decode_all(data, be_u8)?;
+            DhcpOption::DhcpMessageType(match MessageType::from(t) {
+                Ok(x) => x,
+                Err(_) => {
+                    return Err(Error::new(
+                        data,
+                        DecodeErrorKind::UnrecognizedMessageType(t),
+                    ))
+                }
+            })
+        }
+        SERVER_IDENTIFIER => DhcpOption::ServerIdentifier(decode_all(data, decode_ipv4)?),
         PARAMETER_REQUEST_LIST => DhcpOption::ParameterRequestList(data.to_vec()),
-        REQUESTED_IP_ADDRESS => DhcpOption::RequestedIpAddress(decode_ipv4(data)?.1),
-        HOST_NAME => DhcpOption::HostName(match std::str::from_utf8(data) {
-            Ok(s) => s.to_string(),
-            Err(_) => return Err(nom::Err::Error(Err::NonUtf8String)),
-        }),
-        ROUTER => DhcpOption::Router(many0(decode_ipv4)(data)?.1),
-        DOMAIN_NAME_SERVER => DhcpOption::DomainNameServer(many0(decode_ipv4)(data)?.1),
-        IP_ADDRESS_LEASE_TIME => DhcpOption::IpAddressLeaseTime(be_u32(data)?.1),
-        SUBNET_MASK => DhcpOption::SubnetMask(decode_ipv4(data)?.1),
-        MESSAGE => DhcpOption::Message(match std::str::from_utf8(data) {
-            Ok(s) => s.to_string(),
-            Err(_) => return Err(nom::Err::Error(Err::NonUtf8String)),
-        }),
+        MESSAGE => DhcpOption::Message(decode_string(data)?),
+        MAXIMUM_DHCP_MESSAGE_SIZE => DhcpOption::MaximumDhcpMessageSize(decode_all(data, be_u16)?),
+        RENEWAL_TIME_VALUE => DhcpOption::RenewalTimeValue(decode_all(data, be_u32)?),
+        REBINDING_TIME_VALUE => DhcpOption::RebindingTimeValue(decode_all(data, be_u32)?),
+        VENDOR_SPECIFIC_INFORMATION => DhcpOption::VendorSpecificInformation(data.to_vec()),
+        VENDOR_CLASS_IDENTIFIER => DhcpOption::VendorClassIdentifier(data.to_vec()),
+        TFTP_SERVER_NAME => DhcpOption::TftpServerName(decode_string(data)?),
+        BOOTFILE_NAME => DhcpOption::BootfileName(decode_string(data)?),
+        TZ_POSIX_STRING => DhcpOption::TzPosixString(decode_string(data)?),
+        TZ_DATABASE_STRING => DhcpOption::TzDatabaseString(decode_string(data)?),
+        CLIENT_IDENTIFIER => match data.split_first() {
+            Some((&htype, id)) if !id.is_empty() => DhcpOption::ClientIdentifier {
+                htype,
+                id: id.to_vec(),
+            },
+            _ => return Err(Error::new(data, DecodeErrorKind::InvalidLength(data.len()))),
+        },
+        USER_CLASS => DhcpOption::UserClass(decode_user_classes(data)),
+        CLIENT_ARCHITECTURE => DhcpOption::ClientArchitecture(
+            decode_all(data, many0(be_u16))?
+                .into_iter()
+                .map(Architecture::from)
+                .collect(),
+        ),
+        CLIENT_FQDN => match data {
+            [flags, rcode1, rcode2, name @ ..] => DhcpOption::ClientFqdn {
+                flags: *flags,
+                rcode1: *rcode1,
+                rcode2: *rcode2,
+                name: if flags & FQDN_FLAG_E != 0 {
+                    decode_fqdn_name(name)?
+                } else {
+                    decode_string(name)?
+                },
+            },
+            _ => return Err(Error::new(data, DecodeErrorKind::InvalidLength(data.len()))),
+        },
+        DOMAIN_SEARCH => DhcpOption::DomainSearch(decode_domain_names(data)?),
+        CLASSLESS_ROUTE_FORMAT => {
+            DhcpOption::ClasslessStaticRoute(decode_all(data, decode_classless_routes)?)
+        }
+        MS_CLASSLESS_ROUTE_FORMAT => {
+            DhcpOption::MsClasslessStaticRoute(decode_all(data, decode_classless_routes)?)
+        }
+        RELAY_AGENT_INFORMATION => {
+            DhcpOption::RelayAgentInformation(decode_all(data, decode_relay_agent_information)?)
+        }
+        V_I_VENDOR_CLASS => DhcpOption::VendorIdentifyingVendorClass(
+            decode_all(data, |d| {
+                decode_enterprise_blocks(d, decode_vendor_class_data)
+            })?
+            .into_iter()
+            .map(|(enterprise, data)| VendorClass { enterprise, data })
+            .collect(),
+        ),
+        V_I_VENDOR_SPECIFIC_INFORMATION => DhcpOption::VendorIdentifyingVendorSpecific(
+            decode_all(data, |d| {
+                decode_enterprise_blocks(d, decode_vendor_sub_option)
+            })?
+            .into_iter()
+            .map(|(enterprise, options)| VendorOptions {
+                enterprise,
+                options,
+            })
+            .collect(),
+        ),
         _ => DhcpOption::Unrecognized(RawDhcpOption {
             code,
             data: data.to_vec(),
         }),
     };
-    Ok((input, option))
+    Ok(option)
+}
+
+/// Parses the options in an options area, up to and including END, skipping PAD.
+/// If lenient, the end of the area is accepted in place of a missing END.
+fn decode_options(mut input: &[u8], lenient: bool) -> IResult<'_, Vec<(u8, &[u8])>> {
+    let mut chunks = Vec::new();
+    loop {
+        match input.first() {
+            Some(&PAD) => input = &input[1..],
+            Some(&END) => return Ok((&input[1..], chunks)),
+            Some(_) => {
+                let (rest, chunk) = decode_raw_option(input)?;
+                chunks.push(chunk);
+                input = rest;
+            }
+            None if lenient => return Ok((input, chunks)),
+            None => return Error::fail(input, DecodeErrorKind::MissingEnd),
+        }
+    }
+}
+
+/// Parses encapsulated sub-options (RFC 2132 section 8.4), skipping PAD and stopping at an
+/// optional END, with error offsets relative to data.
+pub(crate) fn decode_encapsulated(data: &[u8]) -> Result<Vec<RawDhcpOption>, DecodeError> {
+    match decode_options(data, true) {
+        Ok((&[], chunks)) => Ok(chunks
+            .into_iter()
+            .map(|(code, data)| RawDhcpOption {
+                code,
+                data: data.to_vec(),
+            })
+            .collect()),
+        Ok((rest, _)) => Err(DecodeError {
+            offset: data.len() - rest.len(),
+            option: None,
+            kind: DecodeErrorKind::InvalidLength(data.len()),
+        }),
+        Err(e) => Err(to_decode_error(e, data)),
+    }
 }
 
 /// Parses Packet from byte array
-fn decode(input: &[u8]) -> IResult<&[u8], Packet> {
+fn decode(input: &[u8], lenient: bool) -> IResult<'_, Packet> {
     let (options_input, input) = take(236u32)(input)?;
 
     let (input, reply) = decode_reply(input)?;
-    let (input, _htype) = take(1u8)(input)?;
+    let (input, htype) = be_u8(input)?;
+    let hlen_input = input;
     let (input, hlen) = be_u8(input)?;
     let (input, hops) = be_u8(input)?;
     let (input, xid) = be_u32(input)?;
@@ -110,15 +852,83 @@ fn decode(input: &[u8]) -> IResult<&[u8], Packet> {
     let (input, siaddr) = decode_ipv4(input)?;
     let (input, giaddr) = decode_ipv4(input)?;
 
-    if hlen != 6 {
-        return Err(nom::Err::Error(Err::InvalidHlen));
+    if hlen as usize > CHADDR_LEN {
+        return Error::fail(hlen_input, DecodeErrorKind::InvalidHlen(hlen));
+    }
+    let (input, chaddr) = take(CHADDR_LEN)(input)?;
+    let chaddr = HardwareAddress::new(htype, &chaddr[..hlen as usize]).unwrap();
+    let (input, sname) = take(SNAME_LEN)(input)?;
+    let (_, file) = take(FILE_LEN)(input)?;
+
+    if !options_input.starts_with(&COOKIE) {
+        // BOOTP vendor area without RFC 1048 vendor extensions
+        let (sname, file) = (
+            decode_cstr(sname, lenient)?.1,
+            decode_cstr(file, lenient)?.1,
+        );
+        return Ok((
+            &[],
+            Packet {
+                reply,
+                hops,
+                xid,
+                secs,
+                flags: Flags(flags),
+                ciaddr,
+                yiaddr,
+                siaddr,
+                giaddr,
+                chaddr,
+                sname,
+                file,
+                options: Vec::new(),
+                vend: Some(options_input.to_vec()),
+            },
+        ));
     }
-    let (_, chaddr) = take(6u8)(input)?;
+    let (input, _) = tag(COOKIE)(options_input)?;
+
+    let (input, mut chunks) = decode_options(input, lenient)?;
 
-    let input = options_input;
-    let (input, _) = tag(COOKIE)(input)?;
+    // Option overload (RFC 2132 9.3), options continue in file and then sname
+    let overload = match chunks.iter().position(|&(code, _)| code == OVERLOAD) {
+        Some(i) => match chunks.remove(i).1 {
+            &[v @ OVERLOAD_FILE..=OVERLOAD_BOTH] => v,
+            data => {
+                return Err(nom::Err::Error(Error {
+                    input: data,
+                    option: Some(OVERLOAD),
+                    kind: DecodeErrorKind::InvalidOverload,
+                }))
+            }
+        },
+        None => 0,
+    };
+    let file = if overload & OVERLOAD_FILE != 0 {
+        chunks.extend(decode_options(file, lenient)?.1);
+        None
+    } else {
+        decode_cstr(file, lenient)?.1
+    };
+    let sname = if overload & OVERLOAD_SNAME != 0 {
+        chunks.extend(decode_options(sname, lenient)?.1);
+        None
+    } else {
+        decode_cstr(sname, lenient)?.1
+    };
 
-    let (input, (options, _)) = many_till(decode_option, tag(&[END]))(input)?;
+    let mut options = Vec::new();
+    for (code, data, concatenated) in concatenate_options(chunks) {
+        let option = match decode_concatenated(code, data, concatenated.as_deref()) {
+            Ok(option) => option,
+            Err(_) if lenient => DhcpOption::Unrecognized(RawDhcpOption {
+                code,
+                data: concatenated.unwrap_or_else(|| data.to_vec()),
+            }),
+            Err(e) => return Err(e),
+        };
+        options.push(option);
+    }
 
     Ok((
         input,
@@ -126,33 +936,74 @@ fn decode(input: &[u8]) -> IResult<&[u8], Packet> {
             reply,
             hops,
             secs,
-            broadcast: flags & 128 == 128,
+            flags: Flags(flags),
             ciaddr,
             yiaddr,
             siaddr,
             giaddr,
+            sname,
+            file,
             options,
-            chaddr: [
-                chaddr[0], chaddr[1], chaddr[2], chaddr[3], chaddr[4], chaddr[5],
-            ],
+            chaddr,
             xid,
+            vend: None,
         },
     ))
 }
 
+/// Joins the data of options sharing a code (RFC 3396), keeping the order of first appearance.
+/// Returns each option's first instance along with the joined data if it was split.
+#[allow(clippy::type_complexity)]
+fn concatenate_options(chunks: Vec<(u8, &[u8])>) -> Vec<(u8, &[u8], Option<Vec<u8>>)> {
+    let mut options: Vec<(u8, &[u8], Option<Vec<u8>>)> = Vec::with_capacity(chunks.len());
+    for (code, data) in chunks {
+        match options.iter_mut().find(|o| o.0 == code) {
+            Some((_, first, concatenated)) => concatenated
+                .get_or_insert_with(|| first.to_vec())
+                .extend_from_slice(data),
+            None => options.push((code, data, None)),
+        }
+    }
+    options
+}
+
+/// Interprets option data, which may have been joined from several instances of the option.
+/// Errors within joined data are reported at the first instance.
+fn decode_concatenated<'a>(
+    code: u8,
+    first: &'a [u8],
+    concatenated: Option<&[u8]>,
+) -> Result<DhcpOption, nom::Err<Error<'a>>> {
+    let option = match concatenated {
+        None => decode_option_data(code, first),
+        Some(data) => decode_option_data(code, data).map_err(|e| {
+            map_error(e, |e| Error {
+                input: first,
+                option: e.option,
+                kind: e.kind,
+            })
+        }),
+    };
+    option.map_err(in_option(code))
+}
+
 impl Packet {
-    pub fn from(input: &[u8]) -> Result<Packet, nom::Err<Err<&[u8]>>> {
-        Ok(decode(input)?.1)
+    pub fn from(input: &[u8]) -> Result<Packet, DecodeError> {
+        Ok(Packet::decode(input, false)?.1)
+    }
+
+    /// Parses Packet from byte array, also returning any bytes following the END option.
+    /// If lenient, options missing END are accepted, as sent by some embedded clients, options
+    /// whose data is invalid for their code are kept as Unrecognized, and sname and file are
+    /// decoded even if unterminated or not UTF-8.
+    /// Without the DHCP magic cookie, the packet is BOOTP and its vendor area is kept in vend.
+    pub fn decode(input: &[u8], lenient: bool) -> Result<(&[u8], Packet), DecodeError> {
+        decode(input, lenient).map_err(|e| to_decode_error(e, input))
     }
 
     /// Extracts requested option payload from packet if available
     pub fn option(&self, code: u8) -> Option<&DhcpOption> {
-        for option in &self.options {
-            if option.code() == code {
-                return Some(&option);
-            }
-        }
-        None
+        self.options.iter().find(|option| option.code() == code)
     }
 
     /// Convenience function for extracting a packet's message type.
@@ -167,53 +1018,2459 @@ impl Packet {
         }
     }
 
-    /// Creates byte array DHCP packet
-    pub fn encode<'c>(&'c self, p: &'c mut [u8]) -> &[u8] {
-        p[..12].clone_from_slice(&[
-            (if self.reply { BOOT_REPLY } else { BOOT_REQUEST }),
-            1,
-            6,
-            self.hops,
-            ((self.xid >> 24) & 0xFF) as u8,
-            ((self.xid >> 16) & 0xFF) as u8,
-            ((self.xid >> 8) & 0xFF) as u8,
-            (self.xid & 0xFF) as u8,
-            (self.secs >> 8) as u8,
-            (self.secs & 255) as u8,
-            (if self.broadcast { 128 } else { 0 }),
-            0,
-        ]);
-        p[12..16].clone_from_slice(&self.ciaddr.octets());
-        p[16..20].clone_from_slice(&self.yiaddr.octets());
-        p[20..24].clone_from_slice(&self.siaddr.octets());
-        p[24..28].clone_from_slice(&self.giaddr.octets());
-        p[28..34].clone_from_slice(&self.chaddr);
-        p[34..236].clone_from_slice(&[0; 202]);
-        p[236..240].clone_from_slice(&COOKIE);
+    /// Maximum DHCP message size (excluding IP and UDP headers) that the sender of this packet
+    /// is willing to accept, as given by MAXIMUM_DHCP_MESSAGE_SIZE or the RFC 2131 minimum.
+    /// BOOTP clients, which send no DHCP_MESSAGE_TYPE, only accept BOOTP_MESSAGE_SIZE.
+    pub fn max_message_size(&self) -> usize {
+        match self.option(MAXIMUM_DHCP_MESSAGE_SIZE) {
+            Some(DhcpOption::MaximumDhcpMessageSize(size)) => {
+                MIN_MESSAGE_SIZE.max((*size as usize).saturating_sub(IP_UDP_HEADER_LEN))
+            }
+            _ if self.option(DHCP_MESSAGE_TYPE).is_none() => BOOTP_MESSAGE_SIZE,
+            _ => MIN_MESSAGE_SIZE,
+        }
+    }
 
-        let mut length: usize = 240;
+    /// Checks the packet against RFC 2131 (Tables 3 and 5) for its message type, returning
+    /// every rule it breaks. This is stricter than PacketBuilder::build, which shares its
+    /// required and forbidden options but not the lists of only allowed options, the
+    /// DHCPREQUEST state rules or the checks of option data.
+    /// Repeated instances of an option are joined when decoding (RFC 3396), so
+    /// DuplicateOption is only found here in packets built in code. Use PacketRef::validate
+    /// to also check received packets for a repeated DHCP_MESSAGE_TYPE.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (i, option) in self.options.iter().enumerate() {
+            let code = option.code();
+            let first = self.options.iter().position(|o| o.code() == code);
+            if first == Some(i) && self.options[i + 1..].iter().any(|o| o.code() == code) {
+                violations.push(Violation::DuplicateOption(code));
+            }
+            if let Some(kind) = option_data_error(option) {
+                violations.push(Violation::InvalidOption(code, kind));
+            }
+        }
+
+        let msg_type = match self.option(DHCP_MESSAGE_TYPE) {
+            Some(DhcpOption::DhcpMessageType(t)) => *t,
+            _ => {
+                violations.push(Violation::MissingOption(DHCP_MESSAGE_TYPE));
+                return violations;
+            }
+        };
+        let (required, forbidden, allowed) = option_rules(msg_type);
+        for &code in required {
+            if self.option(code).is_none() {
+                violations.push(Violation::MissingOption(code));
+            }
+        }
         for option in &self.options {
-            let option = option.to_raw();
-            p[length] = option.code;
-            p[length + 1] = option.data.len() as u8;
-            p[length + 2..length + 2 + option.data.len()].clone_from_slice(&option.data);
-            length += 2 + option.data.len();
+            let code = option.code();
+            let unexpected = forbidden.contains(&code)
+                || matches!(allowed, Some(allowed) if !allowed.contains(&code));
+            if unexpected && code != RELAY_AGENT_INFORMATION {
+                violations.push(Violation::UnexpectedOption(code));
+            }
         }
-        p[length] = END;
-        length += 1;
-        if length < 272 {
-            // Pad to min size
-            p[length..272].clone_from_slice(&[PAD; 32][..272 - length]);
-            length = 272
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        if msg_type == MessageType::Request {
+            // Table 5 by client state: SELECTING and INIT-REBOOT leave ciaddr zero and request
+            // an address, RENEWING and REBINDING set ciaddr and send neither option.
+            if self.ciaddr == unspecified {
+                if self.option(REQUESTED_IP_ADDRESS).is_none() {
+                    violations.push(Violation::MissingOption(REQUESTED_IP_ADDRESS));
+                }
+            } else {
+                for code in [SERVER_IDENTIFIER, REQUESTED_IP_ADDRESS] {
+                    if self.option(code).is_some() {
+                        violations.push(Violation::UnexpectedOption(code));
+                    }
+                }
+            }
+        }
+
+        let from_server = matches!(
+            msg_type,
+            MessageType::Offer | MessageType::Ack | MessageType::Nak
+        );
+        if self.reply != from_server {
+            violations.push(Violation::WrongOp);
+        }
+        let ciaddr_zero = matches!(
+            msg_type,
+            MessageType::Discover | MessageType::Decline | MessageType::Offer | MessageType::Nak
+        );
+        if ciaddr_zero && self.ciaddr != unspecified {
+            violations.push(Violation::NonZeroField("ciaddr"));
+        }
+        let ciaddr_set = matches!(msg_type, MessageType::Release | MessageType::Inform);
+        if ciaddr_set && self.ciaddr == unspecified {
+            violations.push(Violation::MissingField("ciaddr"));
+        }
+        if msg_type == MessageType::Offer && self.yiaddr == unspecified {
+            violations.push(Violation::MissingField("yiaddr"));
+        }
+        if !from_server || msg_type == MessageType::Nak {
+            if self.yiaddr != unspecified {
+                violations.push(Violation::NonZeroField("yiaddr"));
+            }
+            if self.siaddr != unspecified {
+                violations.push(Violation::NonZeroField("siaddr"));
+            }
+        }
+        if msg_type == MessageType::Nak {
+            if self.sname.is_some() {
+                violations.push(Violation::NonZeroField("sname"));
+            }
+            if self.file.is_some() {
+                violations.push(Violation::NonZeroField("file"));
+            }
         }
-        &p[..length]
+        violations
     }
-}
 
-const COOKIE: [u8; 4] = [99, 130, 83, 99];
+    /// Creates byte array DHCP packet.
+    /// Panics if the packet does not fit in p, see try_encode.
+    pub fn encode<'c>(&'c self, p: &'c mut [u8]) -> &'c [u8] {
+        match self.try_encode(p) {
+            Ok(encoded) => encoded,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Creates a DHCP packet of at most max_size bytes, see try_encode.
+    /// Use max_message_size() of the packet being replied to as max_size.
+    pub fn encode_to_vec(&self, max_size: usize) -> Result<Vec<u8>, EncodeError> {
+        let mut p = vec![0; max_size.min(MAX_PACKET_SIZE)];
+        let length = self.try_encode(&mut p)?.len();
+        p.truncate(length);
+        Ok(p)
+    }
+
+    /// Creates byte array DHCP packet, no larger than p.
+    /// When the options do not fit, unused sname and file fields are overloaded with them
+    /// (RFC 2132 9.3).
+    pub fn try_encode<'c>(&'c self, p: &'c mut [u8]) -> Result<&'c [u8], EncodeError> {
+        if let Some(vend) = &self.vend {
+            return self.try_encode_bootp(p, vend);
+        }
+        // BOOTP packets with vendor extensions are padded to the full BOOTP vendor area
+        let min_size = match self.option(DHCP_MESSAGE_TYPE) {
+            Some(_) => MIN_PACKET_SIZE,
+            None => BOOTP_MESSAGE_SIZE,
+        };
+        let options = self
+            .options
+            .iter()
+            .map(|o| o.try_to_raw())
+            .collect::<Result<Vec<_>, _>>()?;
+        let chunks = split_options(&options);
+        let options_len: usize = chunks.iter().map(|(_, data)| 2 + data.len()).sum();
+        let size = min_size.max(240 + options_len + 1);
+
+        let areas = if size <= p.len() {
+            vec![0; chunks.len()]
+        } else {
+            let mut capacities = [p.len().saturating_sub(240 + 3 + 1), 0, 0];
+            if self.file.is_none() {
+                capacities[1] = FILE_LEN - 1;
+            }
+            if self.sname.is_none() {
+                capacities[2] = SNAME_LEN - 1;
+            }
+            match overload_areas(&chunks, &capacities) {
+                Some(areas) if p.len() >= min_size => areas,
+                _ => {
+                    return Err(EncodeError::TooLarge {
+                        size,
+                        limit: p.len(),
+                    })
+                }
+            }
+        };
+
+        self.encode_header(p)?;
+        p[236..240].clone_from_slice(&COOKIE);
+
+        let mut length: usize = 240;
+        let mut ends = [0, 108, 44];
+        let overload = areas.iter().fold(0, |acc, &a| acc | AREA_OVERLOAD[a]);
+        if overload != 0 {
+            p[length..length + 3].clone_from_slice(&[OVERLOAD, 1, overload]);
+            length += 3;
+        }
+        for (&(code, data), area) in chunks.iter().zip(areas) {
+            let pos = if area == 0 {
+                &mut length
+            } else {
+                &mut ends[area]
+            };
+            p[*pos] = code;
+            p[*pos + 1] = data.len() as u8;
+            p[*pos + 2..*pos + 2 + data.len()].clone_from_slice(data);
+            *pos += 2 + data.len();
+        }
+        for &area in &[1, 2] {
+            if overload & AREA_OVERLOAD[area] != 0 {
+                p[ends[area]] = END;
+            }
+        }
+        p[length] = END;
+        length += 1;
+        if length < min_size {
+            // Pad to min size
+            for b in p[length..min_size].iter_mut() {
+                *b = PAD;
+            }
+            length = min_size
+        }
+        Ok(&p[..length])
+    }
+
+    /// Writes the fixed size header fields, up to the vendor area.
+    fn encode_header(&self, p: &mut [u8]) -> Result<(), EncodeError> {
+        p[..12].clone_from_slice(&[
+            (if self.reply { BOOT_REPLY } else { BOOT_REQUEST }),
+            self.chaddr.htype,
+            self.chaddr.len,
+            self.hops,
+            ((self.xid >> 24) & 0xFF) as u8,
+            ((self.xid >> 16) & 0xFF) as u8,
+            ((self.xid >> 8) & 0xFF) as u8,
+            (self.xid & 0xFF) as u8,
+            (self.secs >> 8) as u8,
+            (self.secs & 255) as u8,
+            (self.flags.0 >> 8) as u8,
+            (self.flags.0 & 255) as u8,
+        ]);
+        p[12..16].clone_from_slice(&self.ciaddr.octets());
+        p[16..20].clone_from_slice(&self.yiaddr.octets());
+        p[20..24].clone_from_slice(&self.siaddr.octets());
+        p[24..28].clone_from_slice(&self.giaddr.octets());
+        p[28..44].clone_from_slice(&self.chaddr.addr);
+        if !encode_cstr(&mut p[44..108], &self.sname) {
+            return Err(EncodeError::InvalidSname);
+        }
+        if !encode_cstr(&mut p[108..236], &self.file) {
+            return Err(EncodeError::InvalidFile);
+        }
+        Ok(())
+    }
+
+    /// Encodes a BOOTP packet with its vendor area in place of the magic cookie and options.
+    fn try_encode_bootp<'c>(&self, p: &'c mut [u8], vend: &[u8]) -> Result<&'c [u8], EncodeError> {
+        if !self.options.is_empty() {
+            return Err(EncodeError::OptionsWithVend);
+        }
+        let size = BOOTP_MESSAGE_SIZE.max(236 + vend.len());
+        if size > p.len() {
+            return Err(EncodeError::TooLarge {
+                size,
+                limit: p.len(),
+            });
+        }
+        self.encode_header(p)?;
+        p[236..236 + vend.len()].clone_from_slice(vend);
+        for b in p[236 + vend.len()..size].iter_mut() {
+            *b = 0;
+        }
+        Ok(&p[..size])
+    }
+}
+
+/// One line summary, such as "DHCPREQUEST xid 0x3903f326 chaddr 00:0b:82:01:fc:42".
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message_type() {
+            Ok(mtype) => write!(f, "{}", mtype)?,
+            Err(_) if self.reply => write!(f, "BOOTREPLY")?,
+            Err(_) => write!(f, "BOOTREQUEST")?,
+        }
+        write!(f, " xid 0x{:08x} chaddr {}", self.xid, self.chaddr)?;
+        for (name, addr) in [
+            ("ciaddr", self.ciaddr),
+            ("yiaddr", self.yiaddr),
+            ("siaddr", self.siaddr),
+            ("giaddr", self.giaddr),
+        ] {
+            if !addr.is_unspecified() {
+                write!(f, " {} {}", name, addr)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Packet {
+    /// Returns a multi-line Display of the header fields and options, like dhcpdump.
+    pub fn dump(&self) -> Dump<'_> {
+        Dump(self)
+    }
+}
+
+pub struct Dump<'a>(&'a Packet);
+
+impl fmt::Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let p = self.0;
+        if p.reply {
+            writeln!(f, "    OP: {} (BOOTREPLY)", BOOT_REPLY)?;
+        } else {
+            writeln!(f, "    OP: {} (BOOTREQUEST)", BOOT_REQUEST)?;
+        }
+        writeln!(f, " HTYPE: {}", p.chaddr.htype())?;
+        writeln!(f, "  HLEN: {}", p.chaddr.as_bytes().len())?;
+        writeln!(f, "  HOPS: {}", p.hops)?;
+        writeln!(f, "   XID: {:08x}", p.xid)?;
+        writeln!(f, "  SECS: {}", p.secs)?;
+        if p.flags.broadcast() {
+            writeln!(f, " FLAGS: {:04x} (broadcast)", p.flags.0)?;
+        } else {
+            writeln!(f, " FLAGS: {:04x}", p.flags.0)?;
+        }
+        writeln!(f, "CIADDR: {}", p.ciaddr)?;
+        writeln!(f, "YIADDR: {}", p.yiaddr)?;
+        writeln!(f, "SIADDR: {}", p.siaddr)?;
+        writeln!(f, "GIADDR: {}", p.giaddr)?;
+        writeln!(f, "CHADDR: {}", p.chaddr)?;
+        writeln!(f, " SNAME: {}", p.sname.as_deref().unwrap_or(""))?;
+        writeln!(f, " FNAME: {}", p.file.as_deref().unwrap_or(""))?;
+        for option in &p.options {
+            let raw = option.to_raw();
+            writeln!(
+                f,
+                "OPTION: {:3} ({:3}) {:<32} {}",
+                raw.code,
+                raw.data.len(),
+                title(raw.code).unwrap_or("Unknown"),
+                option.value()
+            )?;
+        }
+        if let Some(vend) = &p.vend {
+            write!(f, "  VEND: ")?;
+            write_hex(f, vend)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Borrowed view of a DHCP packet for parsing at high rates without allocating.
+/// Header fields are read straight from the buffer and options are decoded on demand.
+#[derive(Clone, Copy, Debug)]
+pub struct PacketRef<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> PacketRef<'a> {
+    /// Checks the fixed size header. Options are checked as they are read.
+    pub fn new(buf: &'a [u8]) -> Result<PacketRef<'a>, DecodeError> {
+        let fail = |offset, kind| {
+            Err(DecodeError {
+                offset,
+                option: None,
+                kind,
+            })
+        };
+        if buf.len() < 236 {
+            return fail(buf.len(), DecodeErrorKind::Truncated);
+        }
+        if buf[0] != BOOT_REQUEST && buf[0] != BOOT_REPLY {
+            return fail(0, DecodeErrorKind::InvalidOpCode(buf[0]));
+        }
+        if buf[2] as usize > CHADDR_LEN {
+            return fail(2, DecodeErrorKind::InvalidHlen(buf[2]));
+        }
+        Ok(PacketRef { buf })
+    }
+
+    pub fn reply(&self) -> bool {
+        self.buf[0] == BOOT_REPLY
+    }
+
+    pub fn hops(&self) -> u8 {
+        self.buf[3]
+    }
+
+    pub fn xid(&self) -> u32 {
+        crate::bytes_u32!(self.buf[4..])
+    }
+
+    pub fn secs(&self) -> u16 {
+        u16::from_be_bytes([self.buf[8], self.buf[9]])
+    }
+
+    pub fn flags(&self) -> Flags {
+        Flags(u16::from_be_bytes([self.buf[10], self.buf[11]]))
+    }
+
+    pub fn ciaddr(&self) -> Ipv4Addr {
+        self.ipv4(12)
+    }
+
+    pub fn yiaddr(&self) -> Ipv4Addr {
+        self.ipv4(16)
+    }
+
+    pub fn siaddr(&self) -> Ipv4Addr {
+        self.ipv4(20)
+    }
+
+    pub fn giaddr(&self) -> Ipv4Addr {
+        self.ipv4(24)
+    }
+
+    fn ipv4(&self, at: usize) -> Ipv4Addr {
+        Ipv4Addr::new(
+            self.buf[at],
+            self.buf[at + 1],
+            self.buf[at + 2],
+            self.buf[at + 3],
+        )
+    }
+
+    pub fn chaddr(&self) -> HardwareAddress {
+        HardwareAddress::new(self.buf[1], &self.buf[28..28 + self.buf[2] as usize]).unwrap()
+    }
+
+    /// Server host name, None if empty or overloaded with options.
+    pub fn sname(&self) -> Result<Option<&'a str>, DecodeError> {
+        self.cstr(44..108, OVERLOAD_SNAME)
+    }
+
+    /// Boot file name, None if empty or overloaded with options.
+    pub fn file(&self) -> Result<Option<&'a str>, DecodeError> {
+        self.cstr(108..236, OVERLOAD_FILE)
+    }
+
+    fn cstr(
+        &self,
+        field: std::ops::Range<usize>,
+        overload: u8,
+    ) -> Result<Option<&'a str>, DecodeError> {
+        if self.overload()? & overload != 0 {
+            return Ok(None);
+        }
+        let start = field.start;
+        let field = &self.buf[field];
+        let fail = |kind| {
+            Err(DecodeError {
+                offset: start,
+                option: None,
+                kind,
+            })
+        };
+        match field.iter().position(|&b| b == 0) {
+            Some(0) => Ok(None),
+            Some(len) => match std::str::from_utf8(&field[..len]) {
+                Ok(s) => Ok(Some(s)),
+                Err(_) => fail(DecodeErrorKind::NonUtf8String),
+            },
+            None => fail(DecodeErrorKind::UnterminatedString),
+        }
+    }
+
+    /// Value of the overload option, 0 if absent. Fails only if the overload option is
+    /// invalid, since other errors end the options area.
+    fn overload(&self) -> Result<u8, DecodeError> {
+        let mut options = self.options();
+        while let Some(chunk) = options.next() {
+            match chunk {
+                Err(e) if e.option == Some(OVERLOAD) => return Err(e),
+                Err(_) => break,
+                Ok(_) if options.area > 0 => break,
+                Ok(_) => {}
+            }
+        }
+        Ok(options.overload)
+    }
+
+    /// Iterates over the code and data of each option instance, including those in overloaded
+    /// sname and file fields. Split options (RFC 3396) are not joined.
+    /// OVERLOAD is consumed to find the overloaded fields and is not returned, as Packet does
+    /// not keep it either; sname and file return None for overloaded fields.
+    /// BOOTP packets without the magic cookie have no options.
+    pub fn options(&self) -> RawOptions<'a> {
+        RawOptions {
+            buf: self.buf,
+            input: self.buf.get(240..).unwrap_or(&[]),
+            area: 0,
+            overload: 0,
+            done: self.vend().is_some(),
+        }
+    }
+
+    /// BOOTP vendor area, if the packet does not have the DHCP magic cookie.
+    pub fn vend(&self) -> Option<&'a [u8]> {
+        match &self.buf[236..] {
+            vend if vend.starts_with(&COOKIE) => None,
+            vend => Some(vend),
+        }
+    }
+
+    /// Data of the first instance of the option, if present and readable.
+    pub fn raw_option(&self, code: u8) -> Option<&'a [u8]> {
+        self.options()
+            .map_while(Result::ok)
+            .find(|&(c, _)| c == code)
+            .map(|(_, data)| data)
+    }
+
+    /// Decodes the option if present, joining split instances (RFC 3396).
+    /// Always None for OVERLOAD, see options.
+    pub fn option(&self, code: u8) -> Option<Result<DhcpOption, DecodeError>> {
+        let mut first = None;
+        let mut concatenated: Option<Vec<u8>> = None;
+        for chunk in self.options() {
+            match chunk {
+                Ok((c, data)) if c == code => match first {
+                    None => first = Some(data),
+                    Some(f) => concatenated
+                        .get_or_insert_with(|| f.to_vec())
+                        .extend_from_slice(data),
+                },
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(
+            decode_concatenated(code, first?, concatenated.as_deref())
+                .map_err(|e| to_decode_error(e, self.buf)),
+        )
+    }
+
+    /// Convenience function for extracting the message type, if present and valid.
+    pub fn message_type(&self) -> Option<MessageType> {
+        match self.raw_option(DHCP_MESSAGE_TYPE) {
+            Some(&[t]) => MessageType::from(t).ok(),
+            _ => None,
+        }
+    }
+
+    /// Decodes the whole packet.
+    pub fn to_packet(&self) -> Result<Packet, DecodeError> {
+        Packet::from(self.buf)
+    }
+
+    /// Checks the received packet like Packet::validate, after decoding it leniently.
+    /// DHCP_MESSAGE_TYPE is counted in the wire data, where it must appear exactly once,
+    /// since decoding joins repeated instances into one option.
+    pub fn validate(&self) -> Result<Vec<Violation>, DecodeError> {
+        let mut violations = Vec::new();
+        let mut message_types = 0;
+        for chunk in self.options() {
+            if chunk?.0 == DHCP_MESSAGE_TYPE {
+                message_types += 1;
+            }
+        }
+        if message_types > 1 {
+            violations.push(Violation::DuplicateOption(DHCP_MESSAGE_TYPE));
+        }
+        let (_, p) = Packet::decode(self.buf, true)?;
+        violations.extend(p.validate());
+        Ok(violations)
+    }
+}
+
+/// Iterator over a packet's options, see PacketRef::options.
+#[derive(Clone, Debug)]
+pub struct RawOptions<'a> {
+    buf: &'a [u8],
+    input: &'a [u8],
+    area: usize,
+    overload: u8,
+    done: bool,
+}
+
+impl<'a> RawOptions<'a> {
+    fn fail(&mut self, e: nom::Err<Error<'a>>) -> Option<Result<(u8, &'a [u8]), DecodeError>> {
+        self.done = true;
+        Some(Err(to_decode_error(e, self.buf)))
+    }
+
+    /// Moves on to the next overloaded area, options continue in file and then sname
+    /// (RFC 2132 9.3).
+    fn next_area(&mut self) {
+        if self.area < 1 && self.overload & OVERLOAD_FILE != 0 {
+            self.area = 1;
+            self.input = &self.buf[108..236];
+        } else if self.area < 2 && self.overload & OVERLOAD_SNAME != 0 {
+            self.area = 2;
+            self.input = &self.buf[44..108];
+        } else {
+            self.done = true;
+        }
+    }
+}
+
+impl<'a> Iterator for RawOptions<'a> {
+    type Item = Result<(u8, &'a [u8]), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.input.first() {
+                Some(&PAD) => self.input = &self.input[1..],
+                Some(&END) => self.next_area(),
+                Some(_) => match decode_raw_option(self.input) {
+                    Ok((rest, (OVERLOAD, data))) if self.area == 0 => {
+                        self.input = rest;
+                        match data {
+                            &[v @ OVERLOAD_FILE..=OVERLOAD_BOTH] => self.overload = v,
+                            _ => {
+                                return self.fail(in_option(OVERLOAD)(Error::new(
+                                    data,
+                                    DecodeErrorKind::InvalidOverload,
+                                )))
+                            }
+                        }
+                    }
+                    Ok((rest, chunk)) => {
+                        self.input = rest;
+                        return Some(Ok(chunk));
+                    }
+                    Err(e) => return self.fail(e),
+                },
+                None => return self.fail(Error::new(self.input, DecodeErrorKind::MissingEnd)),
+            }
+        }
+        None
+    }
+}
+
+/// Builds request and reply Packets with sensible defaults, applying the RFC 2131
+/// (Tables 3 and 5) field rules for the message type.
+#[derive(Debug)]
+pub struct PacketBuilder {
+    packet: Packet,
+}
+
+impl PacketBuilder {
+    /// Client request from chaddr with a random xid.
+    pub fn request(chaddr: HardwareAddress) -> PacketBuilder {
+        PacketBuilder {
+            packet: Packet {
+                reply: false,
+                hops: 0,
+                xid: random_xid(),
+                secs: 0,
+                flags: Flags::default(),
+                ciaddr: Ipv4Addr::UNSPECIFIED,
+                yiaddr: Ipv4Addr::UNSPECIFIED,
+                siaddr: Ipv4Addr::UNSPECIFIED,
+                giaddr: Ipv4Addr::UNSPECIFIED,
+                chaddr,
+                sname: None,
+                file: None,
+                options: Vec::new(),
+                vend: None,
+            },
+        }
+    }
+
+    /// Server reply to req, copying its xid, chaddr, giaddr, flags and ciaddr.
+    pub fn reply_to(req: &Packet) -> PacketBuilder {
+        let mut b = PacketBuilder::request(req.chaddr);
+        b.packet.reply = true;
+        b.packet.xid = req.xid;
+        b.packet.flags = req.flags;
+        b.packet.ciaddr = req.ciaddr;
+        b.packet.giaddr = req.giaddr;
+        b
+    }
+
+    pub fn xid(mut self, xid: u32) -> Self {
+        self.packet.xid = xid;
+        self
+    }
+
+    pub fn hops(mut self, hops: u8) -> Self {
+        self.packet.hops = hops;
+        self
+    }
+
+    pub fn secs(mut self, secs: u16) -> Self {
+        self.packet.secs = secs;
+        self
+    }
+
+    pub fn flags(mut self, flags: Flags) -> Self {
+        self.packet.flags = flags;
+        self
+    }
+
+    pub fn broadcast(mut self, broadcast: bool) -> Self {
+        self.packet.flags.set_broadcast(broadcast);
+        self
+    }
+
+    pub fn ciaddr(mut self, addr: Ipv4Addr) -> Self {
+        self.packet.ciaddr = addr;
+        self
+    }
+
+    pub fn yiaddr(mut self, addr: Ipv4Addr) -> Self {
+        self.packet.yiaddr = addr;
+        self
+    }
+
+    pub fn siaddr(mut self, addr: Ipv4Addr) -> Self {
+        self.packet.siaddr = addr;
+        self
+    }
+
+    pub fn giaddr(mut self, addr: Ipv4Addr) -> Self {
+        self.packet.giaddr = addr;
+        self
+    }
+
+    pub fn sname(mut self, sname: &str) -> Self {
+        self.packet.sname = Some(sname.to_string());
+        self
+    }
+
+    pub fn file(mut self, file: &str) -> Self {
+        self.packet.file = Some(file.to_string());
+        self
+    }
+
+    pub fn message_type(self, msg_type: MessageType) -> Self {
+        self.option(DhcpOption::DhcpMessageType(msg_type))
+    }
+
+    /// Adds option, replacing any existing option with the same code.
+    pub fn option(mut self, option: DhcpOption) -> Self {
+        match self
+            .packet
+            .options
+            .iter_mut()
+            .find(|o| o.code() == option.code())
+        {
+            Some(o) => *o = option,
+            None => self.packet.options.push(option),
+        }
+        self
+    }
+
+    /// Adds options, replacing any existing options with the same codes.
+    pub fn options<I: IntoIterator<Item = DhcpOption>>(self, options: I) -> Self {
+        options.into_iter().fold(self, |b, o| b.option(o))
+    }
+
+    /// Sends vend as the BOOTP vendor area in place of the magic cookie and options.
+    pub fn vend(mut self, vend: &[u8]) -> Self {
+        self.packet.vend = Some(vend.to_vec());
+        self
+    }
+
+    /// Builds a BOOTP (RFC 951) packet, which has no DHCP message type.
+    /// Header fields are left as set, with op from request or reply_to.
+    pub fn build_bootp(self) -> Result<Packet, BuildError> {
+        let p = self.packet;
+        if p.option(DHCP_MESSAGE_TYPE).is_some() {
+            return Err(BuildError::UnexpectedOption(DHCP_MESSAGE_TYPE));
+        }
+        if p.vend.is_some() {
+            if let Some(option) = p.options.first() {
+                return Err(BuildError::UnexpectedOption(option.code()));
+            }
+        }
+        Ok(p)
+    }
+
+    /// Checks the packet against RFC 2131 for its message type.
+    /// op is set from the message type and addresses that must be zero are cleared.
+    pub fn build(self) -> Result<Packet, BuildError> {
+        let p = self.packet;
+        let msg_type = match p.option(DHCP_MESSAGE_TYPE) {
+            Some(DhcpOption::DhcpMessageType(t)) => *t,
+            _ => return Err(BuildError::MissingOption(DHCP_MESSAGE_TYPE)),
+        };
+        let has = |code| p.option(code).is_some();
+        let (required, forbidden, _) = option_rules(msg_type);
+        if let Some(&code) = required.iter().find(|&&code| !has(code)) {
+            return Err(BuildError::MissingOption(code));
+        }
+        if let Some(&code) = forbidden.iter().find(|&&code| has(code)) {
+            return Err(BuildError::UnexpectedOption(code));
+        }
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        match msg_type {
+            MessageType::Release | MessageType::Inform if p.ciaddr == unspecified => {
+                Err(BuildError::MissingField("ciaddr"))
+            }
+            MessageType::Offer if p.yiaddr == unspecified => {
+                Err(BuildError::MissingField("yiaddr"))
+            }
+            _ => Ok(set_header_fields(p, msg_type)),
+        }
+    }
+
+    /// Like build, but without checking options or fields, only setting op and clearing
+    /// addresses that must be zero if there is a message type. Server::reply uses this to
+    /// leave the choice of options to its caller.
+    pub(crate) fn build_unchecked(self) -> Packet {
+        match self.packet.option(DHCP_MESSAGE_TYPE) {
+            Some(&DhcpOption::DhcpMessageType(msg_type)) => {
+                set_header_fields(self.packet, msg_type)
+            }
+            _ => self.packet,
+        }
+    }
+}
+
+/// Sets op from the message type and clears addresses that must be zero for it.
+fn set_header_fields(mut p: Packet, msg_type: MessageType) -> Packet {
+    let unspecified = Ipv4Addr::UNSPECIFIED;
+    p.reply = matches!(
+        msg_type,
+        MessageType::Offer | MessageType::Ack | MessageType::Nak
+    );
+    match msg_type {
+        MessageType::Discover | MessageType::Decline | MessageType::Offer => p.ciaddr = unspecified,
+        MessageType::Nak => {
+            p.ciaddr = unspecified;
+            p.yiaddr = unspecified;
+            p.siaddr = unspecified;
+        }
+        _ => {}
+    }
+    if !p.reply {
+        p.yiaddr = unspecified;
+        p.siaddr = unspecified;
+    }
+    p
+}
+
+/// Reasons PacketBuilder cannot build a Packet.
+#[derive(Debug, PartialEq)]
+pub enum BuildError {
+    /// Option with this code is required for the message type.
+    MissingOption(u8),
+    /// Option with this code must not be sent with the message type.
+    UnexpectedOption(u8),
+    /// Header field must be set for the message type.
+    MissingField(&'static str),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::MissingOption(code) => write!(f, "missing option {}", code),
+            BuildError::UnexpectedOption(code) => write!(f, "unexpected option {}", code),
+            BuildError::MissingField(field) => write!(f, "{} must be set", field),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// RFC 2131 rule broken by a Packet, as found by Packet::validate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// Option with this code is required for the message type.
+    MissingOption(u8),
+    /// Option with this code must not be sent with the message type.
+    UnexpectedOption(u8),
+    /// Option with this code appears more than once.
+    DuplicateOption(u8),
+    /// Option data is not valid for its code, such as having the wrong length.
+    InvalidOption(u8, DecodeErrorKind),
+    /// op is BOOTREPLY for a client message or BOOTREQUEST for a server message.
+    WrongOp,
+    /// Header field must be set for the message type.
+    MissingField(&'static str),
+    /// Header field must be zero for the message type.
+    NonZeroField(&'static str),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::MissingOption(code) => write!(f, "missing option {}", code),
+            Violation::UnexpectedOption(code) => write!(f, "unexpected option {}", code),
+            Violation::DuplicateOption(code) => write!(f, "duplicate option {}", code),
+            Violation::InvalidOption(code, kind) => write!(f, "{} in option {}", kind, code),
+            Violation::WrongOp => write!(f, "op does not match message type"),
+            Violation::MissingField(field) => write!(f, "{} must be set", field),
+            Violation::NonZeroField(field) => write!(f, "{} must be zero", field),
+        }
+    }
+}
+
+/// Options that RFC 2131 (Tables 3 and 5) requires and forbids for a message type, and the
+/// only options it allows, where all others are forbidden. Used by PacketBuilder::build and
+/// Packet::validate. CLIENT_IDENTIFIER is allowed in server messages, as RFC 6842 requires it
+/// to be echoed.
+#[allow(clippy::type_complexity)]
+fn option_rules(msg_type: MessageType) -> (&'static [u8], &'static [u8], Option<&'static [u8]>) {
+    const SERVER_FORBIDDEN: &[u8] = &[
+        REQUESTED_IP_ADDRESS,
+        PARAMETER_REQUEST_LIST,
+        MAXIMUM_DHCP_MESSAGE_SIZE,
+    ];
+    match msg_type {
+        MessageType::Discover => (&[], &[SERVER_IDENTIFIER], None),
+        MessageType::Request => (&[], &[], None),
+        MessageType::Decline => (
+            &[REQUESTED_IP_ADDRESS, SERVER_IDENTIFIER],
+            &[IP_ADDRESS_LEASE_TIME],
+            Some(&[
+                DHCP_MESSAGE_TYPE,
+                REQUESTED_IP_ADDRESS,
+                SERVER_IDENTIFIER,
+                CLIENT_IDENTIFIER,
+                MESSAGE,
+            ]),
+        ),
+        MessageType::Release => (
+            &[SERVER_IDENTIFIER],
+            &[REQUESTED_IP_ADDRESS],
+            Some(&[
+                DHCP_MESSAGE_TYPE,
+                SERVER_IDENTIFIER,
+                CLIENT_IDENTIFIER,
+                MESSAGE,
+            ]),
+        ),
+        MessageType::Inform => (
+            &[],
+            &[
+                REQUESTED_IP_ADDRESS,
+                IP_ADDRESS_LEASE_TIME,
+                SERVER_IDENTIFIER,
+            ],
+            None,
+        ),
+        MessageType::Offer => (
+            &[IP_ADDRESS_LEASE_TIME, SERVER_IDENTIFIER],
+            SERVER_FORBIDDEN,
+            None,
+        ),
+        MessageType::Ack => (&[SERVER_IDENTIFIER], SERVER_FORBIDDEN, None),
+        MessageType::Nak => (
+            &[SERVER_IDENTIFIER],
+            &[IP_ADDRESS_LEASE_TIME],
+            Some(&[
+                DHCP_MESSAGE_TYPE,
+                SERVER_IDENTIFIER,
+                MESSAGE,
+                CLIENT_IDENTIFIER,
+                VENDOR_CLASS_IDENTIFIER,
+            ]),
+        ),
+    }
+}
+
+/// Smallest data length RFC 2132 and later option RFCs allow for variable length options.
+/// Fixed length options are checked by decoding.
+fn min_option_len(code: u8) -> usize {
+    match code {
+        ROUTER
+        | TIME_SERVER
+        | NAME_SERVER
+        | DOMAIN_NAME_SERVER
+        | LOG_SERVER
+        | COOKIE_SERVER
+        | LPR_SERVER
+        | IMPRESS_SERVER
+        | RESOURCE_LOCATION_SERVER
+        | NETWORK_INFORMATION_SERVERS
+        | NETWORK_TIME_PROTOCOL_SERVERS
+        | NETBIOS_OVER_TCPIP_NAME_SERVER
+        | NETBIOS_OVER_TCPIP_DATAGRAM_DISTRIBUTION_SERVER
+        | XWINDOW_SYSTEM_FONT_SERVER
+        | XWINDOW_SYSTEM_DISPLAY_MANAGER
+        | NETWORK_INFORMATION_SERVICEPLUS_SERVERS
+        | SIMPLE_MAIL_TRANSPORT_PROTOCOL
+        | POST_OFFICE_PROTOCOL_SERVER
+        | NETWORK_NEWS_TRANSPORT_PROTOCOL
+        | DEFAULT_WORLD_WIDE_WEB_SERVER
+        | DEFAULT_FINGER_SERVER
+        | DEFAULT_INTERNET_RELAY_CHAT_SERVER
+        | STREETTALK_SERVER
+        | STREETTALK_DIRECTORY_ASSISTANCE => 4,
+        POLICY_FILTER | STATIC_ROUTE => 8,
+        PATH_MTU_PLATEAU_TABLE | CLIENT_IDENTIFIER | CLIENT_ARCHITECTURE => 2,
+        CLIENT_FQDN => 3,
+        HOST_NAME
+        | MERIT_DUMP_FILE
+        | DOMAIN_NAME
+        | ROOT_PATH
+        | EXTENSIONS_PATH
+        | NETWORK_INFORMATION_SERVICE_DOMAIN
+        | VENDOR_SPECIFIC_INFORMATION
+        | NETBIOS_OVER_TCPIP_SCOPE
+        | NETWORK_INFORMATION_SERVICEPLUS_DOMAIN
+        | PARAMETER_REQUEST_LIST
+        | MESSAGE
+        | VENDOR_CLASS_IDENTIFIER
+        | TFTP_SERVER_NAME
+        | BOOTFILE_NAME
+        | USER_CLASS
+        | RELAY_AGENT_INFORMATION
+        | TZ_POSIX_STRING
+        | TZ_DATABASE_STRING
+        | DOMAIN_SEARCH
+        | CLASSLESS_ROUTE_FORMAT
+        | V_I_VENDOR_CLASS
+        | V_I_VENDOR_SPECIFIC_INFORMATION
+        | MS_CLASSLESS_ROUTE_FORMAT => 1,
+        _ => 0,
+    }
+}
+
+/// Checks that an option's encoded data has a valid length and decodes as its code.
+fn option_data_error(option: &DhcpOption) -> Option<DecodeErrorKind> {
+    if let Err(EncodeError::ItemTooLong { len, .. }) = option.try_to_raw() {
+        return Some(DecodeErrorKind::InvalidLength(len));
+    }
+    let raw = option.to_raw();
+    if raw.data.len() < min_option_len(raw.code) {
+        return Some(DecodeErrorKind::InvalidLength(raw.data.len()));
+    }
+    decode_option_data(raw.code, &raw.data)
+        .err()
+        .map(|e| to_decode_error(e, &raw.data).kind)
+}
+
+/// Random transaction ID, seeded from the process' random hash keys and the time.
+fn random_xid() -> u32 {
+    let mut h = RandomState::new().build_hasher();
+    if let Ok(t) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        h.write_u128(t.as_nanos());
+    }
+    h.finish() as u32
+}
+
+/// Reasons a Packet cannot be encoded.
+#[derive(Debug, PartialEq)]
+pub enum EncodeError {
+    /// Packet needs size bytes without overload, which does not fit within limit even with
+    /// sname and file overloaded.
+    TooLarge { size: usize, limit: usize },
+    /// sname does not fit its field (including NUL terminator) or contains NUL.
+    InvalidSname,
+    /// file does not fit its field (including NUL terminator) or contains NUL.
+    InvalidFile,
+    /// Options cannot be sent in a BOOTP packet whose vend replaces the magic cookie.
+    OptionsWithVend,
+    /// An item of len bytes within option is too long for its length byte.
+    ItemTooLong { option: u8, len: usize },
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EncodeError::TooLarge { size, limit } => write!(
+                f,
+                "packet of {} bytes exceeds limit of {} bytes",
+                size, limit
+            ),
+            EncodeError::InvalidSname => write!(f, "sname is too long or contains NUL"),
+            EncodeError::InvalidFile => write!(f, "file is too long or contains NUL"),
+            EncodeError::OptionsWithVend => write!(f, "options cannot be sent with vend"),
+            EncodeError::ItemTooLong { option, len } => {
+                write!(f, "item of {} bytes is too long for option {}", len, option)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Writes a string into a fixed size header field, NUL terminating and zero filling the remainder.
+/// Returns false if the string does not fit or contains NUL.
+fn encode_cstr(field: &mut [u8], s: &Option<String>) -> bool {
+    let s = s.as_deref().unwrap_or("").as_bytes();
+    if s.len() >= field.len() || s.contains(&0) {
+        return false;
+    }
+    field[..s.len()].clone_from_slice(s);
+    for b in field[s.len()..].iter_mut() {
+        *b = 0;
+    }
+    true
+}
+
+/// Splits options longer than 255 bytes into multiple instances of the same code (RFC 3396).
+fn split_options(options: &[RawDhcpOption]) -> Vec<(u8, &[u8])> {
+    let mut chunks = Vec::with_capacity(options.len());
+    for option in options {
+        if option.data.is_empty() {
+            chunks.push((option.code, &option.data[..]));
+        }
+        for data in option.data.chunks(255) {
+            chunks.push((option.code, data));
+        }
+    }
+    chunks
+}
+
+/// Assigns options, in order, to the options (0), file (1) and sname (2) areas.
+/// Returns None if they do not fit within the areas' capacities.
+fn overload_areas(chunks: &[(u8, &[u8])], capacities: &[usize]) -> Option<Vec<usize>> {
+    let mut areas = Vec::with_capacity(chunks.len());
+    let (mut area, mut used) = (0, 0);
+    for (_, data) in chunks {
+        let len = 2 + data.len();
+        while used + len > capacities[area] {
+            area += 1;
+            used = 0;
+            if area == capacities.len() {
+                return None;
+            }
+        }
+        used += len;
+        areas.push(area);
+    }
+    Some(areas)
+}
+
+const CHADDR_LEN: usize = 16;
+const SNAME_LEN: usize = 64;
+const FILE_LEN: usize = 128;
+
+// ARP hardware types (htype)
+pub const HTYPE_ETHERNET: u8 = 1;
+pub const HTYPE_IEEE802: u8 = 6;
+pub const HTYPE_INFINIBAND: u8 = 32;
+
+const COOKIE: [u8; 4] = [99, 130, 83, 99];
 
 const BOOT_REQUEST: u8 = 1; // From Client;
 const BOOT_REPLY: u8 = 2; // From Server;
 
+const OVERLOAD_FILE: u8 = 1;
+const OVERLOAD_SNAME: u8 = 2;
+const OVERLOAD_BOTH: u8 = 3;
+const AREA_OVERLOAD: [u8; 3] = [0, OVERLOAD_FILE, OVERLOAD_SNAME];
+
+/// Smallest DHCP message every client must accept (RFC 2131, 576 byte IP datagram)
+pub const MIN_MESSAGE_SIZE: usize = 548;
+/// Size of a BOOTP message with its 64 byte vendor area (RFC 951)
+pub const BOOTP_MESSAGE_SIZE: usize = 300;
+const IP_UDP_HEADER_LEN: usize = 28;
+/// Smallest encoded packet, as expected by some BOOTP relay agents (RFC 1542)
+const MIN_PACKET_SIZE: usize = 272;
+/// Largest DHCP message that fits in a UDP datagram
+const MAX_PACKET_SIZE: usize = 65535 - IP_UDP_HEADER_LEN;
+
 const END: u8 = 255;
 const PAD: u8 = 0;
+
+/// Longest domain name in presentation format, excluding the trailing dot.
+const MAX_DOMAIN_NAME_LEN: usize = 253;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Packet {
+        Packet {
+            reply: true,
+            hops: 0,
+            xid: 0x12345678,
+            secs: 0,
+            flags: Flags::default(),
+            ciaddr: Ipv4Addr::new(0, 0, 0, 0),
+            yiaddr: Ipv4Addr::new(192, 168, 0, 180),
+            siaddr: Ipv4Addr::new(192, 168, 0, 76),
+            giaddr: Ipv4Addr::new(0, 0, 0, 0),
+            chaddr: HardwareAddress::ethernet([0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]),
+            sname: Some("tftp.example.com".to_string()),
+            file: Some("pxelinux.0".to_string()),
+            options: vec![
+                DhcpOption::DhcpMessageType(MessageType::Offer),
+                DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 76)),
+            ],
+            vend: None,
+        }
+    }
+
+    #[test]
+    fn sname_file_round_trip() {
+        let p = sample();
+        let mut buf = [0; 1500];
+        let encoded = p.encode(&mut buf);
+        assert_eq!(&encoded[44..61], b"tftp.example.com\0");
+        assert_eq!(&encoded[108..119], b"pxelinux.0\0");
+        assert_eq!(Packet::from(encoded).ok(), Some(p));
+    }
+
+    #[test]
+    fn empty_sname_file() {
+        let mut p = sample();
+        p.sname = None;
+        p.file = None;
+        let mut buf = [0; 1500];
+        let encoded = p.encode(&mut buf);
+        assert!(encoded[34..236].iter().all(|&b| b == 0));
+        assert_eq!(Packet::from(encoded).ok(), Some(p));
+    }
+
+    #[test]
+    fn unterminated_sname() {
+        let mut buf = [0; 1500];
+        let mut encoded = sample().encode(&mut buf).to_vec();
+        for b in encoded[44..108].iter_mut() {
+            *b = b'a';
+        }
+        assert_eq!(
+            Packet::from(&encoded),
+            Err(DecodeError {
+                offset: 44,
+                option: None,
+                kind: DecodeErrorKind::UnterminatedString
+            })
+        );
+        let p = Packet::decode(&encoded, true).unwrap().1;
+        assert_eq!(p.sname, Some("a".repeat(64)));
+
+        encoded[44] = 0xff;
+        encoded[45] = 0;
+        assert_eq!(
+            Packet::from(&encoded).unwrap_err().kind,
+            DecodeErrorKind::NonUtf8String
+        );
+        let p = Packet::decode(&encoded, true).unwrap().1;
+        assert_eq!(p.sname, Some("\u{fffd}".to_string()));
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut buf = [0; 1500];
+        let encoded = sample().encode(&mut buf).to_vec();
+
+        let mut bad_op = encoded.clone();
+        bad_op[0] = 3;
+        let e = Packet::from(&bad_op).unwrap_err();
+        assert_eq!((e.offset, e.kind), (0, DecodeErrorKind::InvalidOpCode(3)));
+
+        let e = Packet::from(&encoded[..100]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::Truncated);
+
+        // Server identifier (at 243) with only 2 bytes of address
+        let mut short = encoded[..243].to_vec();
+        short.extend(&[SERVER_IDENTIFIER, 2, 192, 168, END]);
+        let e = Packet::from(&short).unwrap_err();
+        assert_eq!(
+            e,
+            DecodeError {
+                offset: 245,
+                option: Some(SERVER_IDENTIFIER),
+                kind: DecodeErrorKind::InvalidLength(2)
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "invalid option length 2 at byte 245 in option 54 (Server Identifier)"
+        );
+
+        // Option length runs past the end of the packet
+        let mut long = encoded[..243].to_vec();
+        long.extend(&[HOST_NAME, 10, b'a']);
+        let e = Packet::from(&long).unwrap_err();
+        assert_eq!(
+            (e.option, e.kind),
+            (Some(HOST_NAME), DecodeErrorKind::Truncated)
+        );
+
+        let e = Packet::from(&encoded[..243]).unwrap_err();
+        assert_eq!((e.offset, e.kind), (243, DecodeErrorKind::MissingEnd));
+
+        let e = decode_option(&[END]).unwrap_err();
+        assert_eq!((e.offset, e.kind), (0, DecodeErrorKind::UnexpectedEnd));
+        let e = decode_option(&[DHCP_MESSAGE_TYPE, 1, 9]).unwrap_err();
+        assert_eq!(
+            (e.offset, e.option, e.kind),
+            (
+                2,
+                Some(DHCP_MESSAGE_TYPE),
+                DecodeErrorKind::UnrecognizedMessageType(9)
+            )
+        );
+    }
+
+    #[test]
+    fn file_too_long() {
+        let mut p = sample();
+        p.file = Some("a".repeat(FILE_LEN));
+        assert_eq!(p.encode_to_vec(1500), Err(EncodeError::InvalidFile));
+    }
+
+    #[test]
+    fn too_large() {
+        let mut p = sample();
+        p.options.push(DhcpOption::HostName("a".repeat(400)));
+        assert_eq!(
+            p.encode_to_vec(MIN_MESSAGE_SIZE),
+            Err(EncodeError::TooLarge {
+                size: 654,
+                limit: MIN_MESSAGE_SIZE
+            })
+        );
+        assert_eq!(
+            p.try_encode(&mut [0; 100]).err(),
+            Some(EncodeError::TooLarge {
+                size: 654,
+                limit: 100
+            })
+        );
+        assert_eq!(p.encode_to_vec(1500).unwrap().len(), 654);
+    }
+
+    #[test]
+    fn overload_round_trip() {
+        let mut p = sample();
+        p.sname = None;
+        p.file = None;
+        // 60 routers (242 bytes) and 20 DNS servers (82 bytes) exceed the 308 byte options area
+        p.options.push(DhcpOption::Router(
+            (0..60).map(|i| Ipv4Addr::new(10, 0, 0, i)).collect(),
+        ));
+        p.options.push(DhcpOption::DomainNameServer(
+            (0..20).map(|i| Ipv4Addr::new(10, 0, 1, i)).collect(),
+        ));
+        let mut buf = [0; 1500];
+        let encoded = p.try_encode(&mut buf[..MIN_MESSAGE_SIZE]).unwrap();
+        assert!(encoded.len() <= MIN_MESSAGE_SIZE);
+        assert_eq!(&encoded[240..243], &[OVERLOAD, 1, OVERLOAD_FILE]);
+        assert_eq!(encoded[108], DOMAIN_NAME_SERVER);
+        let r = PacketRef::new(encoded).unwrap();
+        assert_eq!(r.option(OVERLOAD), None);
+        assert_eq!(r.file(), Ok(None));
+        assert_eq!(Packet::from(encoded).ok(), Some(p));
+    }
+
+    #[test]
+    fn overload_not_used_when_fields_set() {
+        let p = sample();
+        let encoded = p.encode_to_vec(MIN_MESSAGE_SIZE).unwrap();
+        assert_ne!(encoded[240], OVERLOAD);
+    }
+
+    #[test]
+    fn decode_overload_sname() {
+        let mut buf = [0; 1500];
+        let mut encoded = sample().encode(&mut buf).to_vec();
+        encoded[44..108].clone_from_slice(&[0; 64]);
+        encoded[44..48].clone_from_slice(&[HOST_NAME, 1, b'a', END]);
+        // Replace trailing padding with the overload option
+        let end = encoded.iter().rposition(|&b| b == END).unwrap();
+        encoded[end..end + 4].clone_from_slice(&[OVERLOAD, 1, OVERLOAD_SNAME, END]);
+        let p = Packet::from(&encoded).ok().unwrap();
+        assert_eq!(p.sname, None);
+        assert_eq!(p.option(OVERLOAD), None);
+        assert_eq!(
+            p.option(HOST_NAME),
+            Some(&DhcpOption::HostName("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn long_option_split_and_joined() {
+        let mut p = sample();
+        let routers: Vec<Ipv4Addr> = (0..100).map(|i| Ipv4Addr::new(10, 0, 0, i)).collect();
+        p.options.push(DhcpOption::Router(routers));
+        let mut buf = [0; 1500];
+        let encoded = p.encode(&mut buf);
+        // Message type (3) and server identifier (6) precede the split router option
+        assert_eq!(&encoded[249..251], &[ROUTER, 255]);
+        assert_eq!(&encoded[506..508], &[ROUTER, 145]);
+        assert_eq!(Packet::from(encoded).ok(), Some(p));
+    }
+
+    #[test]
+    fn decode_concatenates_split_options() {
+        let mut buf = [0; 1500];
+        let mut encoded = sample().encode(&mut buf).to_vec();
+        let end = encoded.iter().rposition(|&b| b == END).unwrap();
+        encoded.truncate(end);
+        encoded.extend(&[HOST_NAME, 2, b'a', b'b', ROUTER, 0, HOST_NAME, 1, b'c', END]);
+        let p = Packet::from(&encoded).ok().unwrap();
+        assert_eq!(p.options.len(), 4);
+        assert_eq!(p.options[2], DhcpOption::HostName("abc".to_string()));
+        assert_eq!(p.options[3], DhcpOption::Router(vec![]));
+    }
+
+    #[test]
+    fn pad_and_trailing() {
+        let mut buf = [0; 1500];
+        let mut encoded = sample().encode(&mut buf)[..243].to_vec();
+        encoded.extend(&[
+            PAD,
+            PAD,
+            SERVER_IDENTIFIER,
+            4,
+            192,
+            168,
+            0,
+            76,
+            PAD,
+            END,
+            1,
+            2,
+        ]);
+        let (trailing, p) = Packet::decode(&encoded, false).unwrap();
+        assert_eq!(p, sample());
+        assert_eq!(trailing, &[1, 2]);
+    }
+
+    #[test]
+    fn lenient_missing_end() {
+        let mut buf = [0; 1500];
+        let mut encoded = sample().encode(&mut buf)[..243].to_vec();
+        encoded.extend(&[SERVER_IDENTIFIER, 4, 192, 168, 0, 76]);
+        assert!(Packet::from(&encoded).is_err());
+        let (trailing, p) = Packet::decode(&encoded, true).unwrap();
+        assert_eq!(p, sample());
+        assert!(trailing.is_empty());
+    }
+
+    #[test]
+    fn lenient_invalid_option() {
+        let mut p = sample();
+        p.options.push(DhcpOption::Unrecognized(RawDhcpOption {
+            code: SUBNET_MASK,
+            data: vec![255, 255, 0],
+        }));
+        let mut buf = [0; 1500];
+        let encoded = p.encode(&mut buf);
+        let e = Packet::from(encoded).unwrap_err();
+        assert_eq!(
+            (e.option, e.kind),
+            (Some(SUBNET_MASK), DecodeErrorKind::InvalidLength(3))
+        );
+        assert_eq!(Packet::decode(encoded, true).unwrap().1, p);
+    }
+
+    #[test]
+    fn infiniband_chaddr() {
+        let mut p = sample();
+        p.chaddr = HardwareAddress::new(HTYPE_INFINIBAND, &[]).unwrap();
+        let mut buf = [0; 1500];
+        let encoded = p.encode(&mut buf);
+        assert_eq!(&encoded[1..3], &[HTYPE_INFINIBAND, 0]);
+        assert!(encoded[28..44].iter().all(|&b| b == 0));
+        assert_eq!(Packet::from(encoded).ok(), Some(p));
+    }
+
+    #[test]
+    fn long_chaddr() {
+        let mut p = sample();
+        p.chaddr = HardwareAddress::new(HTYPE_IEEE802, &[7; 16]).unwrap();
+        let mut buf = [0; 1500];
+        let mut encoded = p.encode(&mut buf).to_vec();
+        assert_eq!(Packet::from(&encoded).ok(), Some(p));
+        assert_eq!(HardwareAddress::new(HTYPE_IEEE802, &[7; 17]), None);
+
+        encoded[2] = 17;
+        let e = Packet::from(&encoded).unwrap_err();
+        assert_eq!((e.offset, e.kind), (2, DecodeErrorKind::InvalidHlen(17)));
+    }
+
+    #[test]
+    fn builder_reply() {
+        let mut req = sample();
+        req.reply = false;
+        req.flags.set_broadcast(true);
+        req.ciaddr = Ipv4Addr::new(192, 168, 0, 10);
+        req.giaddr = Ipv4Addr::new(10, 0, 0, 1);
+        let server_id = DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 76));
+
+        let offer = PacketBuilder::reply_to(&req)
+            .message_type(MessageType::Offer)
+            .yiaddr(Ipv4Addr::new(192, 168, 0, 180))
+            .option(server_id.clone())
+            .option(DhcpOption::IpAddressLeaseTime(60))
+            .option(DhcpOption::IpAddressLeaseTime(3600))
+            .build()
+            .unwrap();
+        assert!(offer.reply);
+        assert_eq!(offer.xid, req.xid);
+        assert_eq!(offer.chaddr, req.chaddr);
+        assert_eq!(offer.giaddr, req.giaddr);
+        assert!(offer.flags.broadcast());
+        assert_eq!(offer.ciaddr, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(
+            offer.options,
+            vec![
+                DhcpOption::DhcpMessageType(MessageType::Offer),
+                server_id.clone(),
+                DhcpOption::IpAddressLeaseTime(3600)
+            ]
+        );
+
+        let ack = PacketBuilder::reply_to(&req)
+            .message_type(MessageType::Ack)
+            .option(server_id.clone())
+            .build()
+            .unwrap();
+        assert_eq!(ack.ciaddr, req.ciaddr);
+
+        let nak = PacketBuilder::reply_to(&req)
+            .message_type(MessageType::Nak)
+            .yiaddr(Ipv4Addr::new(192, 168, 0, 180))
+            .option(server_id)
+            .build()
+            .unwrap();
+        assert_eq!(nak.ciaddr, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(nak.yiaddr, Ipv4Addr::UNSPECIFIED);
+    }
+
+    #[test]
+    fn builder_errors() {
+        let chaddr = HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            PacketBuilder::request(chaddr).build().err(),
+            Some(BuildError::MissingOption(DHCP_MESSAGE_TYPE))
+        );
+        assert_eq!(
+            PacketBuilder::request(chaddr)
+                .message_type(MessageType::Discover)
+                .option(DhcpOption::ServerIdentifier(Ipv4Addr::new(1, 2, 3, 4)))
+                .build()
+                .err(),
+            Some(BuildError::UnexpectedOption(SERVER_IDENTIFIER))
+        );
+        assert_eq!(
+            PacketBuilder::request(chaddr)
+                .message_type(MessageType::Release)
+                .option(DhcpOption::ServerIdentifier(Ipv4Addr::new(1, 2, 3, 4)))
+                .build()
+                .err(),
+            Some(BuildError::MissingField("ciaddr"))
+        );
+        let discover = PacketBuilder::request(chaddr)
+            .message_type(MessageType::Discover)
+            .yiaddr(Ipv4Addr::new(1, 2, 3, 4))
+            .build()
+            .unwrap();
+        assert!(!discover.reply);
+        assert_eq!(discover.yiaddr, Ipv4Addr::UNSPECIFIED);
+    }
+
+    #[test]
+    fn bootp() {
+        let mut raw = vec![0; BOOTP_MESSAGE_SIZE];
+        raw[..12].clone_from_slice(&[1, 1, 6, 0, 0x39, 0x03, 0xf3, 0x26, 0, 0, 0, 0]);
+        raw[28..34].clone_from_slice(&[0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]);
+        raw[236..240].clone_from_slice(b"CMU\0");
+        let p = Packet::from(&raw).unwrap();
+        assert_eq!(p.options, vec![]);
+        assert_eq!(p.vend.as_deref().map(|v| &v[..4]), Some(&b"CMU\0"[..]));
+        assert_eq!(p.max_message_size(), BOOTP_MESSAGE_SIZE);
+        assert_eq!(
+            p.to_string(),
+            "BOOTREQUEST xid 0x3903f326 chaddr 00:0b:82:01:fc:42"
+        );
+        assert_eq!(p.encode_to_vec(p.max_message_size()).unwrap(), raw);
+
+        let r = PacketRef::new(&raw).unwrap();
+        assert_eq!(r.vend(), Some(&raw[236..]));
+        assert_eq!(r.options().count(), 0);
+
+        // RFC 1048 vendor extensions
+        let reply = PacketBuilder::reply_to(&p)
+            .yiaddr(Ipv4Addr::new(192, 168, 0, 20))
+            .option(DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)))
+            .build_bootp()
+            .unwrap();
+        let encoded = reply.encode_to_vec(BOOTP_MESSAGE_SIZE).unwrap();
+        assert_eq!(encoded.len(), BOOTP_MESSAGE_SIZE);
+        assert_eq!(Packet::from(&encoded).unwrap(), reply);
+        assert_eq!(PacketRef::new(&encoded).unwrap().vend(), None);
+
+        assert_eq!(
+            PacketBuilder::reply_to(&p)
+                .message_type(MessageType::Ack)
+                .build_bootp()
+                .err(),
+            Some(BuildError::UnexpectedOption(DHCP_MESSAGE_TYPE))
+        );
+        let mut with_options = p.clone();
+        with_options
+            .options
+            .push(DhcpOption::HostName("printer".to_string()));
+        assert_eq!(
+            with_options.encode_to_vec(BOOTP_MESSAGE_SIZE),
+            Err(EncodeError::OptionsWithVend)
+        );
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(Packet::from(&discover()).unwrap().validate(), vec![]);
+
+        let chaddr = HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]);
+        let mut offer = PacketBuilder::request(chaddr)
+            .message_type(MessageType::Offer)
+            .yiaddr(Ipv4Addr::new(192, 168, 0, 10))
+            .options(vec![
+                DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 1)),
+                DhcpOption::IpAddressLeaseTime(3600),
+            ])
+            .build()
+            .unwrap();
+        assert_eq!(offer.validate(), vec![]);
+
+        offer.reply = false;
+        offer.ciaddr = Ipv4Addr::new(192, 168, 0, 10);
+        offer.options.remove(2);
+        offer
+            .options
+            .push(DhcpOption::ServerIdentifier(Ipv4Addr::new(10, 0, 0, 1)));
+        offer.options.push(DhcpOption::ParameterRequestList(vec![]));
+        offer.options.push(DhcpOption::Router(vec![]));
+        offer.options.push(DhcpOption::Unrecognized(RawDhcpOption {
+            code: SUBNET_MASK,
+            data: vec![255, 255, 0],
+        }));
+        assert_eq!(
+            offer.validate(),
+            vec![
+                Violation::DuplicateOption(SERVER_IDENTIFIER),
+                Violation::InvalidOption(PARAMETER_REQUEST_LIST, DecodeErrorKind::InvalidLength(0)),
+                Violation::InvalidOption(ROUTER, DecodeErrorKind::InvalidLength(0)),
+                Violation::InvalidOption(SUBNET_MASK, DecodeErrorKind::InvalidLength(3)),
+                Violation::MissingOption(IP_ADDRESS_LEASE_TIME),
+                Violation::UnexpectedOption(PARAMETER_REQUEST_LIST),
+                Violation::WrongOp,
+                Violation::NonZeroField("ciaddr"),
+            ]
+        );
+
+        let mut nak = sample();
+        nak.options = vec![
+            DhcpOption::DhcpMessageType(MessageType::Nak),
+            DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 1)),
+            DhcpOption::IpAddressLeaseTime(3600),
+        ];
+        nak.options.push(DhcpOption::RelayAgentInformation(vec![
+            RelayAgentSubOption::CircuitId(b"eth0".to_vec()),
+        ]));
+        assert_eq!(
+            nak.validate(),
+            vec![
+                Violation::UnexpectedOption(IP_ADDRESS_LEASE_TIME),
+                Violation::NonZeroField("yiaddr"),
+                Violation::NonZeroField("siaddr"),
+                Violation::NonZeroField("sname"),
+                Violation::NonZeroField("file"),
+            ]
+        );
+
+        let selecting = PacketBuilder::request(chaddr)
+            .message_type(MessageType::Request)
+            .option(DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 1)))
+            .build()
+            .unwrap();
+        assert_eq!(
+            selecting.validate(),
+            vec![Violation::MissingOption(REQUESTED_IP_ADDRESS)]
+        );
+        let mut renewing = selecting.clone();
+        renewing.ciaddr = Ipv4Addr::new(192, 168, 0, 10);
+        renewing
+            .options
+            .push(DhcpOption::RequestedIpAddress(renewing.ciaddr));
+        assert_eq!(
+            renewing.validate(),
+            vec![
+                Violation::UnexpectedOption(SERVER_IDENTIFIER),
+                Violation::UnexpectedOption(REQUESTED_IP_ADDRESS),
+            ]
+        );
+        renewing.options.truncate(1);
+        assert_eq!(renewing.validate(), vec![]);
+
+        nak.options.truncate(2);
+        nak.yiaddr = Ipv4Addr::UNSPECIFIED;
+        nak.siaddr = Ipv4Addr::UNSPECIFIED;
+        nak.sname = None;
+        assert_eq!(nak.validate(), vec![Violation::NonZeroField("file")]);
+
+        // Repeated DHCP_MESSAGE_TYPE on the wire is joined by decoding
+        let discover = PacketBuilder::request(chaddr)
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+        let mut buf = [0; 1500];
+        let mut encoded = discover.encode(&mut buf).to_vec();
+        let end = 240 + 3;
+        assert_eq!(encoded[end], END);
+        encoded.splice(end..end, [DHCP_MESSAGE_TYPE, 1, 1]);
+        let r = PacketRef::new(&encoded).unwrap();
+        let violations = r.validate().unwrap();
+        assert_eq!(violations[0], Violation::DuplicateOption(DHCP_MESSAGE_TYPE));
+        assert!(violations.contains(&Violation::MissingOption(DHCP_MESSAGE_TYPE)));
+        assert_eq!(
+            PacketRef::new(discover.encode(&mut buf))
+                .unwrap()
+                .validate(),
+            Ok(vec![])
+        );
+
+        let mut untyped = sample();
+        untyped.options.clear();
+        assert_eq!(
+            untyped.validate(),
+            vec![Violation::MissingOption(DHCP_MESSAGE_TYPE)]
+        );
+    }
+
+    #[test]
+    fn packet_ref() {
+        let mut p = sample();
+        p.sname = None;
+        p.options.push(DhcpOption::HostName("a".repeat(20)));
+        p.options.push(DhcpOption::Router(
+            (0..70).map(|i| Ipv4Addr::new(10, 0, 0, i)).collect(),
+        ));
+        let mut buf = [0; 1500];
+        let encoded = p.try_encode(&mut buf[..MIN_MESSAGE_SIZE]).unwrap();
+        let r = PacketRef::new(encoded).unwrap();
+        assert!(r.reply());
+        assert_eq!(r.xid(), p.xid);
+        assert_eq!(r.yiaddr(), p.yiaddr);
+        assert_eq!(r.siaddr(), p.siaddr);
+        assert_eq!(r.chaddr(), p.chaddr);
+        assert_eq!(r.file(), Ok(Some("pxelinux.0")));
+        // Router option is split, with its second instance overloaded into sname
+        assert_eq!(r.sname(), Ok(None));
+        assert_eq!(r.message_type(), Some(MessageType::Offer));
+        let codes: Vec<u8> = r.options().map(|o| o.unwrap().0).collect();
+        assert_eq!(
+            codes,
+            vec![
+                DHCP_MESSAGE_TYPE,
+                SERVER_IDENTIFIER,
+                HOST_NAME,
+                ROUTER,
+                ROUTER
+            ]
+        );
+        assert_eq!(r.option(ROUTER), Some(Ok(p.options[3].clone())));
+        assert_eq!(r.option(MESSAGE), None);
+        assert_eq!(r.to_packet(), Ok(p));
+    }
+
+    #[test]
+    fn rfc2132_options() {
+        let options = vec![
+            DhcpOption::TimeOffset(-3600),
+            DhcpOption::DomainName("example.com".to_string()),
+            DhcpOption::IpForwardingEnableDisable(true),
+            DhcpOption::PolicyFilter(vec![(
+                Ipv4Addr::new(10, 0, 0, 0),
+                Ipv4Addr::new(255, 0, 0, 0),
+            )]),
+            DhcpOption::DefaultIpTimeToLive(64),
+            DhcpOption::PathMtuPlateauTable(vec![1500, 576]),
+            DhcpOption::InterfaceMtu(1500),
+            DhcpOption::BroadcastAddress(Ipv4Addr::new(192, 168, 0, 255)),
+            DhcpOption::NetworkTimeProtocolServers(vec![Ipv4Addr::new(192, 168, 0, 1)]),
+            DhcpOption::MaximumDhcpMessageSize(1500),
+            DhcpOption::RenewalTimeValue(1800),
+            DhcpOption::RebindingTimeValue(3150),
+            DhcpOption::VendorClassIdentifier(b"MSFT 5.0".to_vec()),
+            DhcpOption::TzDatabaseString("Europe/Zurich".to_string()),
+        ];
+        for option in options {
+            let raw = option.to_raw();
+            assert_eq!(raw.code, option.code());
+            let mut bytes = vec![raw.code, raw.data.len() as u8];
+            bytes.extend(&raw.data);
+            assert_eq!(decode_option(&bytes), Ok((&[][..], option)));
+        }
+
+        assert_eq!(
+            DhcpOption::TimeOffset(-1).to_raw().data,
+            vec![0xff, 0xff, 0xff, 0xff]
+        );
+        let e = decode_option(&[INTERFACE_MTU, 3, 5, 220, 0]).unwrap_err();
+        assert_eq!(
+            (e.offset, e.option, e.kind),
+            (2, Some(INTERFACE_MTU), DecodeErrorKind::InvalidLength(3))
+        );
+        let e = decode_option(&[STATIC_ROUTE, 4, 10, 0, 0, 0]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(4));
+    }
+
+    #[test]
+    fn classless_static_route() {
+        let routes = vec![
+            ("0.0.0.0/0".parse().unwrap(), Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.0.0.0/8".parse().unwrap(), Ipv4Addr::new(192, 168, 0, 2)),
+            (
+                "10.17.0.0/20".parse().unwrap(),
+                Ipv4Addr::new(192, 168, 0, 3),
+            ),
+            (
+                "172.16.5.9/32".parse().unwrap(),
+                Ipv4Addr::new(192, 168, 0, 4),
+            ),
+        ];
+        let raw = DhcpOption::ClasslessStaticRoute(routes.clone()).to_raw();
+        #[rustfmt::skip]
+        assert_eq!(
+            raw.data,
+            vec![
+                0, 192, 168, 0, 1,
+                8, 10, 192, 168, 0, 2,
+                20, 10, 17, 0, 192, 168, 0, 3,
+                32, 172, 16, 5, 9, 192, 168, 0, 4,
+            ]
+        );
+        let mut bytes = vec![CLASSLESS_ROUTE_FORMAT, raw.data.len() as u8];
+        bytes.extend(&raw.data);
+        assert_eq!(
+            decode_option(&bytes),
+            Ok((&[][..], DhcpOption::ClasslessStaticRoute(routes.clone())))
+        );
+        bytes[0] = MS_CLASSLESS_ROUTE_FORMAT;
+        assert_eq!(
+            decode_option(&bytes),
+            Ok((&[][..], DhcpOption::MsClasslessStaticRoute(routes)))
+        );
+
+        let e = decode_option(&[CLASSLESS_ROUTE_FORMAT, 5, 33, 10, 0, 0, 1]).unwrap_err();
+        assert_eq!((e.offset, e.kind), (2, DecodeErrorKind::InvalidPrefix(33)));
+        // 10.129.0.0/9 has a host bit set
+        let e =
+            decode_option(&[CLASSLESS_ROUTE_FORMAT, 7, 9, 10, 129, 192, 168, 0, 1]).unwrap_err();
+        assert_eq!((e.offset, e.kind), (2, DecodeErrorKind::InvalidPrefix(9)));
+        let e = decode_option(&[CLASSLESS_ROUTE_FORMAT, 5, 8, 10, 192, 168, 0]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(5));
+    }
+
+    #[test]
+    fn relay_agent_information() {
+        #[rustfmt::skip]
+        let bytes = [
+            RELAY_AGENT_INFORMATION, 34,
+            1, 4, b'e', b't', b'h', b'0',
+            2, 6, 0, 0x1b, 0x21, 0x3c, 0x4d, 0x5e,
+            5, 4, 10, 1, 0, 0,
+            10, 1, 0x80,
+            151, 3, 1, b'v', b'1',
+            152, 0,
+            200, 2, 7, 8,
+        ];
+        let subs = vec![
+            RelayAgentSubOption::CircuitId(b"eth0".to_vec()),
+            RelayAgentSubOption::RemoteId(vec![0, 0x1b, 0x21, 0x3c, 0x4d, 0x5e]),
+            RelayAgentSubOption::LinkSelection(Ipv4Addr::new(10, 1, 0, 0)),
+            RelayAgentSubOption::RelayAgentFlags(RELAY_AGENT_FLAG_UNICAST),
+            RelayAgentSubOption::VirtualSubnetSelection {
+                vss_type: 1,
+                info: b"v1".to_vec(),
+            },
+            RelayAgentSubOption::VirtualSubnetSelectionControl,
+            RelayAgentSubOption::Unrecognized(RawDhcpOption {
+                code: 200,
+                data: vec![7, 8],
+            }),
+        ];
+        let option = DhcpOption::RelayAgentInformation(subs);
+        assert_eq!(decode_option(&bytes), Ok((&[][..], option.clone())));
+        assert_eq!(option.to_raw().data, &bytes[2..]);
+
+        let e = decode_option(&[RELAY_AGENT_INFORMATION, 5, 5, 3, 10, 1, 0]).unwrap_err();
+        assert_eq!(
+            (e.offset, e.option, e.kind),
+            (
+                4,
+                Some(RELAY_AGENT_INFORMATION),
+                DecodeErrorKind::InvalidLength(3)
+            )
+        );
+        let e = decode_option(&[RELAY_AGENT_INFORMATION, 3, 1, 4, 0]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(3));
+
+        let option =
+            DhcpOption::RelayAgentInformation(vec![RelayAgentSubOption::CircuitId(vec![0; 256])]);
+        assert_eq!(
+            option.try_to_raw(),
+            Err(EncodeError::ItemTooLong {
+                option: RELAY_AGENT_INFORMATION,
+                len: 256
+            })
+        );
+        assert_eq!(option.to_raw().data.len(), 257);
+    }
+
+    #[test]
+    fn domain_search() {
+        // RFC 3397 section 3 example
+        let names = vec![
+            "eng.apple.com.".to_string(),
+            "marketing.apple.com".to_string(),
+        ];
+        let raw = DhcpOption::DomainSearch(names).to_raw();
+        let mut expected = vec![3];
+        expected.extend(b"eng");
+        expected.push(5);
+        expected.extend(b"apple");
+        expected.push(3);
+        expected.extend(b"com");
+        expected.push(0);
+        expected.push(9);
+        expected.extend(b"marketing");
+        expected.extend(&[0xc0, 4]);
+        assert_eq!(raw.data, expected);
+
+        let mut bytes = vec![DOMAIN_SEARCH, expected.len() as u8];
+        bytes.extend(&expected);
+        assert_eq!(
+            decode_option(&bytes),
+            Ok((
+                &[][..],
+                DhcpOption::DomainSearch(vec![
+                    "eng.apple.com".to_string(),
+                    "marketing.apple.com".to_string()
+                ])
+            ))
+        );
+
+        // Pointer to itself, forwards, out of bounds, and a reserved label type
+        for data in [
+            &[0xc0, 0][..],
+            &[1, b'a', 0xc0, 4, 0, 0],
+            &[1, b'a', 0xc0, 1],
+            &[0x40, 0],
+            &[3, b'a', b'b'],
+            &[1, b'a'],
+        ] {
+            let mut bytes = vec![DOMAIN_SEARCH, data.len() as u8];
+            bytes.extend(data);
+            let e = decode_option(&bytes).unwrap_err();
+            assert_eq!(e.kind, DecodeErrorKind::InvalidDomainName);
+        }
+    }
+
+    #[test]
+    fn long_domain_search() {
+        // More than 255 bytes, so split into several options (RFC 3396)
+        let names: Vec<String> = (0..30)
+            .map(|i| format!("host{}.site{}.example.org", i, i))
+            .collect();
+        assert!(DhcpOption::DomainSearch(names.clone()).to_raw().data.len() > 255);
+        let mut p = sample();
+        p.options = vec![
+            DhcpOption::DhcpMessageType(MessageType::Offer),
+            DhcpOption::DomainSearch(names.clone()),
+        ];
+        let encoded = p.encode_to_vec(MAX_PACKET_SIZE).unwrap();
+        let decoded = Packet::from(&encoded).unwrap();
+        assert_eq!(
+            decoded.option(DOMAIN_SEARCH),
+            Some(&DhcpOption::DomainSearch(names))
+        );
+    }
+
+    #[test]
+    fn client_identifier() {
+        let duid = Duid::LinkLayerTime {
+            htype: 1,
+            time: 0x2a3b4c5d,
+            addr: vec![0, 0x1b, 0x21, 0x3c, 0x4d, 0x5e],
+        };
+        let option = DhcpOption::rfc4361_client_identifier(7, &duid);
+        #[rustfmt::skip]
+        assert_eq!(
+            option.to_raw().data,
+            vec![
+                255,
+                0, 0, 0, 7,
+                0, 1, 0, 1, 0x2a, 0x3b, 0x4c, 0x5d, 0, 0x1b, 0x21, 0x3c, 0x4d, 0x5e,
+            ]
+        );
+        let mut bytes = vec![CLIENT_IDENTIFIER, 19];
+        bytes.extend(option.to_raw().data);
+        let (_, decoded) = decode_option(&bytes).unwrap();
+        assert_eq!(decoded, option);
+        assert_eq!(decoded.iaid_duid(), Some((7, duid)));
+
+        let ethernet = DhcpOption::ClientIdentifier {
+            htype: HTYPE_ETHERNET,
+            id: vec![0, 0x1b, 0x21, 0x3c, 0x4d, 0x5e],
+        };
+        assert_eq!(ethernet.iaid_duid(), None);
+        let e = decode_option(&[CLIENT_IDENTIFIER, 1, 1]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(1));
+    }
+
+    #[test]
+    fn client_fqdn() {
+        let mut canonical = vec![CLIENT_FQDN, 21, FQDN_FLAG_S | FQDN_FLAG_E, 0, 0, 4];
+        canonical.extend(b"host");
+        canonical.push(7);
+        canonical.extend(b"example");
+        canonical.push(3);
+        canonical.extend(b"com");
+        canonical.push(0);
+        let option = DhcpOption::ClientFqdn {
+            flags: FQDN_FLAG_S | FQDN_FLAG_E,
+            rcode1: 0,
+            rcode2: 0,
+            name: "host.example.com.".to_string(),
+        };
+        assert_eq!(decode_option(&canonical), Ok((&[][..], option.clone())));
+        assert_eq!(option.to_raw().data, &canonical[2..]);
+
+        // Partial name, without the root label
+        let partial = [
+            CLIENT_FQDN,
+            8,
+            FQDN_FLAG_E,
+            255,
+            255,
+            4,
+            b'h',
+            b'o',
+            b's',
+            b't',
+        ];
+        let (_, option) = decode_option(&partial).unwrap();
+        assert_eq!(
+            option,
+            DhcpOption::ClientFqdn {
+                flags: FQDN_FLAG_E,
+                rcode1: 255,
+                rcode2: 255,
+                name: "host".to_string(),
+            }
+        );
+        assert_eq!(option.to_raw().data, &partial[2..]);
+
+        let ascii = [CLIENT_FQDN, 7, FQDN_FLAG_N, 0, 0, b'h', b'o', b's', b't'];
+        let (_, option) = decode_option(&ascii).unwrap();
+        assert_eq!(
+            option,
+            DhcpOption::ClientFqdn {
+                flags: FQDN_FLAG_N,
+                rcode1: 0,
+                rcode2: 0,
+                name: "host".to_string(),
+            }
+        );
+        assert_eq!(option.to_raw().data, &ascii[2..]);
+
+        let e = decode_option(&[CLIENT_FQDN, 5, FQDN_FLAG_E, 0, 0, 4, b'h']).unwrap_err();
+        assert_eq!((e.offset, e.kind), (5, DecodeErrorKind::InvalidDomainName));
+        let e = decode_option(&[CLIENT_FQDN, 2, 0, 0]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(2));
+    }
+
+    #[test]
+    fn vendor_identifying_options() {
+        // CableLabs (4491) eRouter container with two sub-options
+        #[rustfmt::skip]
+        let bytes = [
+            V_I_VENDOR_SPECIFIC_INFORMATION, 19,
+            0, 0, 0x11, 0x8b, 7, 1, 2, 0, 1, 2, 1, 3,
+            0, 0, 0, 9, 2, 5, 0,
+        ];
+        let (_, option) = decode_option(&bytes).unwrap();
+        assert_eq!(
+            option.vendor_options(4491),
+            Some(&VendorOptions {
+                enterprise: 4491,
+                options: vec![
+                    RawDhcpOption {
+                        code: 1,
+                        data: vec![0, 1],
+                    },
+                    RawDhcpOption {
+                        code: 2,
+                        data: vec![3],
+                    },
+                ],
+            })
+        );
+        assert_eq!(
+            option.vendor_options(9).unwrap().option(5),
+            Some(&RawDhcpOption {
+                code: 5,
+                data: vec![],
+            })
+        );
+        assert_eq!(option.vendor_options(311), None);
+        assert_eq!(option.to_raw().data, &bytes[2..]);
+
+        #[rustfmt::skip]
+        let bytes = [
+            V_I_VENDOR_CLASS, 13,
+            0, 0, 0x11, 0x8b, 8, 3, b'e', b'c', b'm', 3, b'e', b'p', b's',
+        ];
+        let (_, option) = decode_option(&bytes).unwrap();
+        assert_eq!(
+            option.vendor_class(4491),
+            Some(&VendorClass {
+                enterprise: 4491,
+                data: vec![b"ecm".to_vec(), b"eps".to_vec()],
+            })
+        );
+        assert_eq!(option.to_raw().data, &bytes[2..]);
+
+        let e = decode_option(&[V_I_VENDOR_CLASS, 7, 0, 0, 0x11, 0x8b, 2, 4, b'e']).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(7));
+    }
+
+    #[test]
+    fn vendor_identifying_split_blocks() {
+        let options: Vec<RawDhcpOption> = (1..=3)
+            .map(|code| RawDhcpOption {
+                code,
+                data: vec![code; 100],
+            })
+            .collect();
+        let option = DhcpOption::VendorIdentifyingVendorSpecific(vec![VendorOptions {
+            enterprise: 4491,
+            options,
+        }]);
+        let data = option.to_raw().data;
+        // Two sub-options fit in the first block, the third starts another
+        assert_eq!(data.len(), 2 * 5 + 3 * 102);
+        assert_eq!(data[4], 204);
+        let mut p = sample();
+        p.options = vec![DhcpOption::DhcpMessageType(MessageType::Offer), option];
+        let encoded = p.encode_to_vec(MAX_PACKET_SIZE).unwrap();
+        assert_eq!(Packet::from(&encoded), Ok(p.clone()));
+
+        // Items must fit a block along with their headers
+        p.options[1] = DhcpOption::VendorIdentifyingVendorSpecific(vec![VendorOptions {
+            enterprise: 4491,
+            options: vec![RawDhcpOption {
+                code: 1,
+                data: vec![0; 254],
+            }],
+        }]);
+        assert_eq!(
+            p.encode_to_vec(MAX_PACKET_SIZE),
+            Err(EncodeError::ItemTooLong {
+                option: V_I_VENDOR_SPECIFIC_INFORMATION,
+                len: 254
+            })
+        );
+        let option = DhcpOption::VendorIdentifyingVendorClass(vec![VendorClass {
+            enterprise: 4491,
+            data: vec![vec![0; 254], vec![0; 255]],
+        }]);
+        assert_eq!(
+            option.try_to_raw(),
+            Err(EncodeError::ItemTooLong {
+                option: V_I_VENDOR_CLASS,
+                len: 255
+            })
+        );
+        assert_eq!(option.to_raw().data.len(), 2 * 5 + 2 * 255);
+    }
+
+    #[test]
+    fn user_class_and_architecture() {
+        let bytes = [
+            USER_CLASS, 9, 3, b'a', b'b', b'c', 4, b'i', b'P', b'X', b'E',
+        ];
+        let option = DhcpOption::UserClass(vec![b"abc".to_vec(), b"iPXE".to_vec()]);
+        assert_eq!(decode_option(&bytes), Ok((&[][..], option.clone())));
+        assert_eq!(option.to_raw().data, &bytes[2..]);
+        let option = DhcpOption::UserClass(vec![vec![b'a'; 256]]);
+        assert_eq!(
+            option.try_to_raw(),
+            Err(EncodeError::ItemTooLong {
+                option: USER_CLASS,
+                len: 256
+            })
+        );
+
+        // iPXE sends its user class without a length
+        let bytes = [USER_CLASS, 4, b'i', b'P', b'X', b'E'];
+        assert_eq!(
+            decode_option(&bytes),
+            Ok((&[][..], DhcpOption::UserClass(vec![b"iPXE".to_vec()])))
+        );
+
+        let bytes = [CLIENT_ARCHITECTURE, 6, 0, 7, 0, 11, 0x12, 0x34];
+        let option = DhcpOption::ClientArchitecture(vec![
+            Architecture::EfiBc,
+            Architecture::EfiArm64,
+            Architecture::Other(0x1234),
+        ]);
+        assert_eq!(decode_option(&bytes), Ok((&[][..], option.clone())));
+        assert_eq!(option.to_raw().data, &bytes[2..]);
+        let e = decode_option(&[CLIENT_ARCHITECTURE, 1, 0]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::InvalidLength(1));
+    }
+
+    /// IPv6-Only Preferred (RFC 8925), as a downstream crate would define it.
+    #[derive(Clone, PartialEq, Debug)]
+    struct Ipv6OnlyPreferred(u32);
+
+    impl ExtensionOption for Ipv6OnlyPreferred {
+        const CODE: u8 = 108;
+        const TITLE: &'static str = "IPv6-Only Preferred";
+
+        fn encode(&self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn decode(data: &[u8]) -> Result<Self, DecodeErrorKind> {
+            match data {
+                [a, b, c, d] => Ok(Ipv6OnlyPreferred(u32::from_be_bytes([*a, *b, *c, *d]))),
+                _ => Err(DecodeErrorKind::InvalidLength(data.len())),
+            }
+        }
+    }
+
+    /// Extension for a code with a built-in decoder, which cannot be registered.
+    #[derive(Clone, PartialEq, Debug)]
+    struct MessageTypeOverride(u8);
+
+    impl ExtensionOption for MessageTypeOverride {
+        const CODE: u8 = DHCP_MESSAGE_TYPE;
+        const TITLE: &'static str = "DHCP Message Type Override";
+
+        fn encode(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn decode(data: &[u8]) -> Result<Self, DecodeErrorKind> {
+            Ok(MessageTypeOverride(data[0]))
+        }
+    }
+
+    #[test]
+    fn extension_option() {
+        assert_eq!(title(108), None);
+        assert_eq!(register_extension::<Ipv6OnlyPreferred>(), Ok(()));
+        assert_eq!(title(108), Some("IPv6-Only Preferred"));
+        assert_eq!(
+            register_extension::<Ipv6OnlyPreferred>(),
+            Err(RegisterError::AlreadyRegistered(108))
+        );
+        assert_eq!(
+            register_extension::<MessageTypeOverride>(),
+            Err(RegisterError::BuiltIn(DHCP_MESSAGE_TYPE))
+        );
+        assert_eq!(
+            decode_option(&[DHCP_MESSAGE_TYPE, 1, 1]),
+            Ok((&[][..], DhcpOption::DhcpMessageType(MessageType::Discover)))
+        );
+
+        let option = DhcpOption::Extension(Extension::new(Ipv6OnlyPreferred(1800)));
+        assert_eq!(option.code(), 108);
+        let mut p = sample();
+        p.options.push(option.clone());
+        let mut buf = [0; 1500];
+        let decoded = Packet::from(p.encode(&mut buf)).unwrap();
+        assert_eq!(decoded, p);
+        match decoded.option(108) {
+            Some(DhcpOption::Extension(ext)) => {
+                assert_eq!(ext.downcast_ref(), Some(&Ipv6OnlyPreferred(1800)));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let e = decode_option(&[108, 2, 0, 0]).unwrap_err();
+        assert_eq!(
+            (e.offset, e.option, e.kind),
+            (2, Some(108), DecodeErrorKind::InvalidLength(2))
+        );
+    }
+
+    #[test]
+    fn display() {
+        let p = Packet::from(&discover()).unwrap();
+        assert_eq!(
+            p.to_string(),
+            "DHCPDISCOVER xid 0x00003d1d chaddr 00:0b:82:01:fc:42"
+        );
+        assert_eq!(
+            p.dump().to_string(),
+            "    OP: 1 (BOOTREQUEST)
+ HTYPE: 1
+  HLEN: 6
+  HOPS: 0
+   XID: 00003d1d
+  SECS: 0
+ FLAGS: 0000
+CIADDR: 0.0.0.0
+YIADDR: 0.0.0.0
+SIADDR: 0.0.0.0
+GIADDR: 0.0.0.0
+CHADDR: 00:0b:82:01:fc:42
+ SNAME: 
+ FNAME: 
+OPTION:  53 (  1) DHCP Message Type                DHCPDISCOVER
+OPTION:  61 (  7) Client-identifier                type 1, 00 0b 82 01 fc 42
+OPTION:  50 (  4) Requested IP Address             0.0.0.0
+OPTION:  55 (  4) Parameter Request List           1 (Subnet Mask), 3 (Router), 6 (Domain Name Server), 42 (Network Time Protocol Servers)
+"
+        );
+
+        assert_eq!(
+            DhcpOption::Router(vec![
+                Ipv4Addr::new(192, 168, 0, 1),
+                Ipv4Addr::new(192, 168, 0, 2)
+            ])
+            .to_string(),
+            "Router: 192.168.0.1, 192.168.0.2"
+        );
+        assert_eq!(
+            DhcpOption::Unrecognized(RawDhcpOption {
+                code: 224,
+                data: vec![1, 0xab],
+            })
+            .to_string(),
+            "Option 224: 01 ab"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut p = Packet::from(&discover()).unwrap();
+        p.options.push(DhcpOption::ClasslessStaticRoute(vec![(
+            "10.0.0.0/8".parse().unwrap(),
+            Ipv4Addr::new(192, 168, 0, 1),
+        )]));
+        let json = serde_json::to_string(&p).unwrap();
+        assert!(json.contains(r#""chaddr":{"htype":1,"addr":"00:0b:82:01:fc:42"}"#));
+        assert!(json.contains(r#"{"DhcpMessageType":"Discover"}"#));
+        assert!(json.contains(r#"{"ClasslessStaticRoute":[["10.0.0.0/8","192.168.0.1"]]}"#));
+        assert_eq!(serde_json::from_str::<Packet>(&json).unwrap(), p);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_option_names() {
+        let options = vec![
+            DhcpOption::ParameterRequestList(vec![SUBNET_MASK, ROUTER, 224]),
+            DhcpOption::Unrecognized(RawDhcpOption {
+                code: 224,
+                data: vec![1],
+            }),
+            DhcpOption::Unrecognized(RawDhcpOption {
+                code: NAME_SERVER,
+                data: vec![],
+            }),
+        ];
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"ParameterRequestList":["Subnet Mask","Router",224]},{"Unrecognized":{"code":224,"data":[1]}},{"Unrecognized":{"code":5,"name":"Name Server","data":[]}}]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<DhcpOption>>(&json).unwrap(),
+            options
+        );
+
+        // Every title maps back to its own code
+        let all = DhcpOption::ParameterRequestList((0..=255).collect());
+        let json = serde_json::to_string(&all).unwrap();
+        assert_eq!(serde_json::from_str::<DhcpOption>(&json).unwrap(), all);
+        assert!(
+            serde_json::from_str::<DhcpOption>(r#"{"ParameterRequestList":["Nope"]}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn packet_ref_errors() {
+        let mut buf = [0; 1500];
+        let mut encoded = sample().encode(&mut buf)[..243].to_vec();
+        let e = PacketRef::new(&encoded[..200]).unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::Truncated);
+
+        encoded.extend(&[SERVER_IDENTIFIER, 2, 192, 168, END]);
+        let r = PacketRef::new(&encoded).unwrap();
+        assert_eq!(r.raw_option(SERVER_IDENTIFIER), Some(&[192, 168][..]));
+        let e = r.option(SERVER_IDENTIFIER).unwrap().unwrap_err();
+        assert_eq!(
+            (e.offset, e.option, e.kind),
+            (
+                245,
+                Some(SERVER_IDENTIFIER),
+                DecodeErrorKind::InvalidLength(2)
+            )
+        );
+
+        encoded.pop();
+        let r = PacketRef::new(&encoded).unwrap();
+        let e = r.options().last().unwrap().unwrap_err();
+        assert_eq!((e.offset, e.kind), (247, DecodeErrorKind::MissingEnd));
+        assert_eq!(r.sname(), Ok(Some("tftp.example.com")));
+
+        // A truncated trailing option does not affect sname and file
+        encoded.truncate(243);
+        encoded.extend(&[SERVER_IDENTIFIER, 4, 192, 168]);
+        let r = PacketRef::new(&encoded).unwrap();
+        let e = r.options().last().unwrap().unwrap_err();
+        assert_eq!(e.kind, DecodeErrorKind::Truncated);
+        assert_eq!(r.sname(), Ok(Some("tftp.example.com")));
+        assert_eq!(r.file(), Ok(Some("pxelinux.0")));
+
+        encoded.truncate(243);
+        encoded.extend(&[OVERLOAD, 1, 4, END]);
+        let r = PacketRef::new(&encoded).unwrap();
+        let e = r.sname().unwrap_err();
+        assert_eq!(
+            (e.option, e.kind),
+            (Some(OVERLOAD), DecodeErrorKind::InvalidOverload)
+        );
+    }
+
+    /// DHCPDISCOVER from Wireshark's dhcp.pcap sample capture (UDP payload).
+    fn discover() -> Vec<u8> {
+        let mut p = vec![0; 272];
+        p[..12].clone_from_slice(&[1, 1, 6, 0, 0x00, 0x00, 0x3d, 0x1d, 0, 0, 0, 0]);
+        p[28..34].clone_from_slice(&[0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]);
+        p[236..265].clone_from_slice(&[
+            0x63, 0x82, 0x53, 0x63, 0x35, 0x01, 0x01, 0x3d, 0x07, 0x01, 0x00, 0x0b, 0x82, 0x01,
+            0xfc, 0x42, 0x32, 0x04, 0x00, 0x00, 0x00, 0x00, 0x37, 0x04, 0x01, 0x03, 0x06, 0x2a,
+            0xff,
+        ]);
+        p
+    }
+
+    #[test]
+    fn captured_discover() {
+        let captured = discover();
+        let p = Packet::from(&captured).unwrap();
+        assert!(!p.reply);
+        assert_eq!(p.xid, 0x3d1d);
+        assert_eq!(p.flags, Flags(0));
+        assert_eq!(p.chaddr.to_string(), "00:0b:82:01:fc:42");
+        assert_eq!(p.message_type(), Ok(MessageType::Discover));
+        assert_eq!(
+            p.option(PARAMETER_REQUEST_LIST),
+            Some(&DhcpOption::ParameterRequestList(vec![1, 3, 6, 42]))
+        );
+        let mut buf = [0; 1500];
+        assert_eq!(p.encode(&mut buf), &captured[..]);
+    }
+
+    #[test]
+    fn flags() {
+        let mut captured = discover();
+        captured[10..12].clone_from_slice(&[0x80, 0x00]);
+        let p = Packet::from(&captured).unwrap();
+        assert!(p.flags.broadcast());
+        assert_eq!(p.flags.reserved(), 0);
+        assert!(PacketRef::new(&captured).unwrap().flags().broadcast());
+
+        // Bit 7 of the low byte is reserved, not broadcast
+        captured[10..12].clone_from_slice(&[0x00, 0x80]);
+        let mut p = Packet::from(&captured).unwrap();
+        assert!(!p.flags.broadcast());
+        assert_eq!(p.flags.reserved(), 0x0080);
+        let mut buf = [0; 1500];
+        assert_eq!(p.encode(&mut buf), &captured[..]);
+
+        p.flags.set_broadcast(true);
+        assert_eq!(&p.encode(&mut buf)[10..12], &[0x80, 0x80]);
+        p.flags.set_broadcast(false);
+        assert_eq!(p.flags, Flags(0x0080));
+    }
+}
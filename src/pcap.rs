@@ -0,0 +1,611 @@
+//! Reading DHCP packets from pcap and pcapng captures, and writing them to pcap.
+//!
+//! Frames are accepted with Ethernet (including VLAN tags), Linux cooked or raw IPv4 link
+//! layers. Only unfragmented IPv4 UDP datagrams to or from ports 67 and 68 are decoded.
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::packet::{DecodeError, Packet, HTYPE_ETHERNET};
+
+/// DHCP packet found in a capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub timestamp: SystemTime,
+    pub src: SocketAddrV4,
+    pub dst: SocketAddrV4,
+    /// Packet decoded leniently from payload, or why it could not be decoded.
+    pub packet: Result<Packet, DecodeError>,
+    /// UDP payload as captured.
+    pub payload: Vec<u8>,
+}
+
+/// Iterates the DHCP packets in a pcap or pcapng capture. Errors are only returned for the
+/// capture itself, while a DHCP payload that fails to decode is returned in its Record.
+pub struct Reader<R> {
+    input: R,
+    format: Format,
+}
+
+enum Format {
+    Pcap {
+        big_endian: bool,
+        nanos: bool,
+        linktype: u32,
+    },
+    PcapNg {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+struct Interface {
+    linktype: u32,
+    /// if_tsresol: a power of 10, or of 2 if the high bit is set.
+    tsresol: u8,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn u16_at(buf: &[u8], at: usize, big_endian: bool) -> u16 {
+    let b = [buf[at], buf[at + 1]];
+    if big_endian {
+        u16::from_be_bytes(b)
+    } else {
+        u16::from_le_bytes(b)
+    }
+}
+
+fn u32_at(buf: &[u8], at: usize, big_endian: bool) -> u32 {
+    let b = [buf[at], buf[at + 1], buf[at + 2], buf[at + 3]];
+    if big_endian {
+        u32::from_be_bytes(b)
+    } else {
+        u32::from_le_bytes(b)
+    }
+}
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_IF_TSRESOL: u16 = 9;
+/// Largest record or block read, well above any real snaplen, so a corrupt or hostile length
+/// cannot cause a huge allocation.
+const MAX_RECORD_LEN: usize = 256 * 1024;
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+const IPPROTO_UDP: u8 = 17;
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+
+impl<R: Read> Reader<R> {
+    /// Reads the capture's file header, detecting pcap or pcapng and its byte order.
+    pub fn new(mut input: R) -> io::Result<Reader<R>> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        let format = if u32::from_be_bytes(magic) == PCAPNG_SECTION_HEADER {
+            let big_endian = read_section_header(&mut input)?;
+            Format::PcapNg {
+                big_endian,
+                interfaces: vec![],
+            }
+        } else {
+            let (big_endian, nanos) = match (u32::from_be_bytes(magic), u32::from_le_bytes(magic)) {
+                (PCAP_MAGIC, _) => (true, false),
+                (PCAP_MAGIC_NANOS, _) => (true, true),
+                (_, PCAP_MAGIC) => (false, false),
+                (_, PCAP_MAGIC_NANOS) => (false, true),
+                _ => return Err(invalid("not a pcap or pcapng file")),
+            };
+            let mut header = [0; 20];
+            input.read_exact(&mut header)?;
+            Format::Pcap {
+                big_endian,
+                nanos,
+                linktype: u32_at(&header, 16, big_endian),
+            }
+        };
+        Ok(Reader { input, format })
+    }
+
+    /// Reads exactly buf.len() bytes, or returns false at the end of input.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.input.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the next captured frame with its timestamp and link type.
+    fn next_frame(&mut self) -> io::Result<Option<(SystemTime, u32, Vec<u8>)>> {
+        match self.format {
+            Format::Pcap {
+                big_endian,
+                nanos,
+                linktype,
+            } => {
+                let mut header = [0; 16];
+                if !self.read_or_eof(&mut header)? {
+                    return Ok(None);
+                }
+                let secs = u32_at(&header, 0, big_endian) as u64;
+                let frac = u32_at(&header, 4, big_endian);
+                let captured = u32_at(&header, 8, big_endian) as usize;
+                if captured > MAX_RECORD_LEN {
+                    return Err(invalid("pcap record length too large"));
+                }
+                let mut frame = vec![0; captured];
+                self.input.read_exact(&mut frame)?;
+                let frac = if nanos {
+                    Duration::from_nanos(frac as u64)
+                } else {
+                    Duration::from_micros(frac as u64)
+                };
+                Ok(Some((
+                    UNIX_EPOCH + Duration::from_secs(secs) + frac,
+                    linktype,
+                    frame,
+                )))
+            }
+            Format::PcapNg { .. } => self.next_pcapng_frame(),
+        }
+    }
+
+    fn next_pcapng_frame(&mut self) -> io::Result<Option<(SystemTime, u32, Vec<u8>)>> {
+        loop {
+            let mut block_type = [0; 4];
+            if !self.read_or_eof(&mut block_type)? {
+                return Ok(None);
+            }
+            if u32::from_be_bytes(block_type) == PCAPNG_SECTION_HEADER {
+                let big_endian = read_section_header(&mut self.input)?;
+                self.format = Format::PcapNg {
+                    big_endian,
+                    interfaces: vec![],
+                };
+                continue;
+            }
+            let (big_endian, interfaces) = match &mut self.format {
+                Format::PcapNg {
+                    big_endian,
+                    interfaces,
+                } => (*big_endian, interfaces),
+                Format::Pcap { .. } => unreachable!(),
+            };
+            let mut len = [0; 4];
+            self.input.read_exact(&mut len)?;
+            let len = u32_at(&len, 0, big_endian) as usize;
+            if len < 12 || len % 4 != 0 || len > MAX_RECORD_LEN {
+                return Err(invalid("invalid pcapng block length"));
+            }
+            // Body, followed by the repeated block length
+            let mut body = vec![0; len - 8];
+            self.input.read_exact(&mut body)?;
+            body.truncate(len - 12);
+
+            match u32_at(&block_type, 0, big_endian) {
+                PCAPNG_INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                    interfaces.push(Interface {
+                        linktype: u16_at(&body, 0, big_endian) as u32,
+                        tsresol: interface_tsresol(&body[8..], big_endian),
+                    });
+                }
+                PCAPNG_ENHANCED_PACKET if body.len() >= 20 => {
+                    let interface = interfaces
+                        .get(u32_at(&body, 0, big_endian) as usize)
+                        .ok_or_else(|| invalid("packet for undescribed pcapng interface"))?;
+                    let ts = (u32_at(&body, 4, big_endian) as u64) << 32
+                        | u32_at(&body, 8, big_endian) as u64;
+                    let captured = u32_at(&body, 12, big_endian) as usize;
+                    let frame = body
+                        .get(20..20 + captured)
+                        .ok_or_else(|| invalid("pcapng packet exceeds its block"))?;
+                    return Ok(Some((
+                        UNIX_EPOCH + timestamp(ts, interface.tsresol),
+                        interface.linktype,
+                        frame.to_vec(),
+                    )));
+                }
+                PCAPNG_SIMPLE_PACKET if body.len() >= 4 => {
+                    let interface = interfaces
+                        .first()
+                        .ok_or_else(|| invalid("packet for undescribed pcapng interface"))?;
+                    let captured = (u32_at(&body, 0, big_endian) as usize).min(body.len() - 4);
+                    // Simple packet blocks have no timestamp
+                    return Ok(Some((
+                        UNIX_EPOCH,
+                        interface.linktype,
+                        body[4..4 + captured].to_vec(),
+                    )));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads the rest of a section header block, after its type, returning its byte order.
+fn read_section_header<R: Read>(input: &mut R) -> io::Result<bool> {
+    let mut header = [0; 8];
+    input.read_exact(&mut header)?;
+    let big_endian = match u32_at(&header, 4, true) {
+        PCAPNG_BYTE_ORDER => true,
+        _ if u32_at(&header, 4, false) == PCAPNG_BYTE_ORDER => false,
+        _ => return Err(invalid("invalid pcapng byte order magic")),
+    };
+    let len = u32_at(&header, 0, big_endian) as usize;
+    if len < 28 || len % 4 != 0 {
+        return Err(invalid("invalid pcapng block length"));
+    }
+    io::copy(&mut input.take((len - 12) as u64), &mut io::sink())?;
+    Ok(big_endian)
+}
+
+/// Finds if_tsresol in an interface description block's options, defaulting to microseconds.
+fn interface_tsresol(mut options: &[u8], big_endian: bool) -> u8 {
+    while options.len() >= 4 {
+        let code = u16_at(options, 0, big_endian);
+        let len = u16_at(options, 2, big_endian) as usize;
+        if code == PCAPNG_IF_TSRESOL && len == 1 && options.len() > 4 {
+            return options[4];
+        }
+        if code == 0 {
+            break;
+        }
+        let padded = 4 + len.div_ceil(4) * 4;
+        options = options.get(padded..).unwrap_or(&[]);
+    }
+    6
+}
+
+fn timestamp(ts: u64, tsresol: u8) -> Duration {
+    let exp = (tsresol & 0x7f) as u32;
+    if tsresol & 0x80 != 0 {
+        let units = 1u128 << exp.min(127);
+        Duration::from_nanos((ts as u128 * 1_000_000_000 / units) as u64)
+    } else {
+        let units = 10u128.pow(exp.min(19));
+        Duration::from_nanos((ts as u128 * 1_000_000_000 / units) as u64)
+    }
+}
+
+/// Finds the DHCP payload of a frame, with its source and destination.
+fn dhcp_payload(linktype: u32, frame: &[u8]) -> Option<(SocketAddrV4, SocketAddrV4, &[u8])> {
+    let ip = match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut at = 12;
+            let mut ethertype = u16_at(frame.get(..at + 2)?, at, true);
+            while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+                at += 4;
+                ethertype = u16_at(frame.get(..at + 2)?, at, true);
+            }
+            if ethertype != ETHERTYPE_IPV4 {
+                return None;
+            }
+            &frame[at + 2..]
+        }
+        LINKTYPE_LINUX_SLL if u16_at(frame.get(..16)?, 14, true) == ETHERTYPE_IPV4 => &frame[16..],
+        LINKTYPE_LINUX_SLL2 if u16_at(frame.get(..20)?, 0, true) == ETHERTYPE_IPV4 => &frame[20..],
+        LINKTYPE_RAW | LINKTYPE_IPV4 => frame,
+        _ => return None,
+    };
+
+    let header_len = ((*ip.first()? & 0x0f) as usize) * 4;
+    if ip[0] >> 4 != 4 || header_len < 20 || ip.len() < header_len + 8 {
+        return None;
+    }
+    // Fragments other than a whole datagram are skipped
+    let fragment = u16_at(ip, 6, true);
+    if ip[9] != IPPROTO_UDP || fragment & 0x3fff != 0 {
+        return None;
+    }
+    let total_len = (u16_at(ip, 2, true) as usize).min(ip.len());
+    let src_ip = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    let dst_ip = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+
+    let udp = ip.get(header_len..total_len)?;
+    if udp.len() < 8 {
+        return None;
+    }
+    let src_port = u16_at(udp, 0, true);
+    let dst_port = u16_at(udp, 2, true);
+    let dhcp_port = |p| p == SERVER_PORT || p == CLIENT_PORT;
+    if !dhcp_port(src_port) && !dhcp_port(dst_port) {
+        return None;
+    }
+    let udp_len = (u16_at(udp, 4, true) as usize).clamp(8, udp.len());
+    Some((
+        SocketAddrV4::new(src_ip, src_port),
+        SocketAddrV4::new(dst_ip, dst_port),
+        &udp[8..udp_len],
+    ))
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        loop {
+            let (timestamp, linktype, frame) = match self.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some((src, dst, payload)) = dhcp_payload(linktype, &frame) {
+                return Some(Ok(Record {
+                    timestamp,
+                    src,
+                    dst,
+                    packet: Packet::decode(payload, true).map(|(_, packet)| packet),
+                    payload: payload.to_vec(),
+                }));
+            }
+        }
+    }
+}
+
+/// Writes DHCP packets to a pcap capture with Ethernet framing.
+pub struct Writer<W> {
+    output: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes the pcap file header.
+    pub fn new(mut output: W) -> io::Result<Writer<W>> {
+        let mut header = Vec::with_capacity(24);
+        header.extend(&PCAP_MAGIC.to_le_bytes());
+        header.extend(&2u16.to_le_bytes());
+        header.extend(&4u16.to_le_bytes());
+        header.extend(&[0; 8]); // thiszone, sigfigs
+        header.extend(&65535u32.to_le_bytes());
+        header.extend(&LINKTYPE_ETHERNET.to_le_bytes());
+        output.write_all(&header)?;
+        Ok(Writer { output })
+    }
+
+    /// Writes packet as a UDP datagram from src to dst. The client's Ethernet address is
+    /// taken from chaddr, and the other end is broadcast for requests or zero for replies.
+    pub fn write(
+        &mut self,
+        timestamp: SystemTime,
+        src: SocketAddrV4,
+        dst: SocketAddrV4,
+        packet: &Packet,
+    ) -> io::Result<()> {
+        let payload = packet
+            .encode_to_vec(usize::MAX)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let client = match packet.chaddr.htype() {
+            HTYPE_ETHERNET if packet.chaddr.as_bytes().len() == 6 => packet.chaddr.as_bytes(),
+            _ => &[0; 6],
+        };
+        let (src_mac, dst_mac) = if packet.reply {
+            (&[0; 6][..], client)
+        } else {
+            (client, &[0xff; 6][..])
+        };
+        let frame = ethernet_frame(src_mac, dst_mac, src, dst, &payload);
+
+        let since_epoch = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut header = Vec::with_capacity(16);
+        header.extend(&(since_epoch.as_secs() as u32).to_le_bytes());
+        header.extend(&since_epoch.subsec_micros().to_le_bytes());
+        header.extend(&(frame.len() as u32).to_le_bytes());
+        header.extend(&(frame.len() as u32).to_le_bytes());
+        self.output.write_all(&header)?;
+        self.output.write_all(&frame)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+/// Wraps payload in Ethernet, IPv4 and UDP headers. The UDP checksum is left as zero,
+/// which IPv4 allows.
+fn ethernet_frame(
+    src_mac: &[u8],
+    dst_mac: &[u8],
+    src: SocketAddrV4,
+    dst: SocketAddrV4,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(42 + payload.len());
+    frame.extend(dst_mac);
+    frame.extend(src_mac);
+    frame.extend(&ETHERTYPE_IPV4.to_be_bytes());
+
+    let ip_start = frame.len();
+    frame.extend(&[0x45, 0]);
+    frame.extend(&((28 + payload.len()) as u16).to_be_bytes());
+    frame.extend(&[0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0]);
+    frame.extend(&src.ip().octets());
+    frame.extend(&dst.ip().octets());
+    let checksum = !frame[ip_start..]
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]) as u32)
+        .fold(0u32, |sum, w| {
+            let sum = sum + w;
+            (sum & 0xffff) + (sum >> 16)
+        }) as u16;
+    frame[ip_start + 10..ip_start + 12].copy_from_slice(&checksum.to_be_bytes());
+
+    frame.extend(&src.port().to_be_bytes());
+    frame.extend(&dst.port().to_be_bytes());
+    frame.extend(&((8 + payload.len()) as u16).to_be_bytes());
+    frame.extend(&[0, 0]);
+    frame.extend(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{DhcpOption, MessageType};
+    use crate::packet::{HardwareAddress, PacketBuilder};
+
+    fn packets() -> Vec<Record> {
+        let discover =
+            PacketBuilder::request(HardwareAddress::ethernet([0, 0x0b, 0x82, 0x01, 0xfc, 0x42]))
+                .xid(0x3903f326)
+                .message_type(MessageType::Discover)
+                .build()
+                .unwrap();
+        let offer = PacketBuilder::reply_to(&discover)
+            .message_type(MessageType::Offer)
+            .yiaddr(Ipv4Addr::new(192, 168, 0, 10))
+            .option(DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 0, 1)))
+            .option(DhcpOption::IpAddressLeaseTime(3600))
+            .build()
+            .unwrap();
+        vec![
+            Record {
+                timestamp: UNIX_EPOCH + Duration::new(1_100_000_000, 123_000),
+                src: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, CLIENT_PORT),
+                dst: SocketAddrV4::new(Ipv4Addr::BROADCAST, SERVER_PORT),
+                payload: discover.encode_to_vec(usize::MAX).unwrap(),
+                packet: Ok(discover),
+            },
+            Record {
+                timestamp: UNIX_EPOCH + Duration::new(1_100_000_001, 0),
+                src: SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), SERVER_PORT),
+                dst: SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 10), CLIENT_PORT),
+                payload: offer.encode_to_vec(usize::MAX).unwrap(),
+                packet: Ok(offer),
+            },
+        ]
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let mut writer = Writer::new(vec![]).unwrap();
+        for r in packets() {
+            let packet = r.packet.as_ref().unwrap();
+            writer.write(r.timestamp, r.src, r.dst, packet).unwrap();
+        }
+        let capture = writer.into_inner();
+        let read: Vec<Record> = Reader::new(&capture[..])
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(read, packets());
+    }
+
+    #[test]
+    fn malformed_packet() {
+        let record = &packets()[0];
+        let mut writer = Writer::new(vec![]).unwrap();
+        let packet = record.packet.as_ref().unwrap();
+        writer
+            .write(record.timestamp, record.src, record.dst, packet)
+            .unwrap();
+        let mut capture = writer.into_inner();
+        // op of the DHCP payload, after the file and record headers and Ethernet, IPv4 and
+        // UDP headers
+        let op = 24 + 16 + 42;
+        capture[op] = 3;
+
+        let read = Reader::new(&capture[..]).unwrap().next().unwrap().unwrap();
+        assert_eq!(read.timestamp, record.timestamp);
+        assert_eq!((read.src, read.dst), (record.src, record.dst));
+        assert_eq!(read.payload[0], 3);
+        assert_eq!(read.payload[1..], record.payload[1..]);
+        assert!(read.packet.is_err());
+    }
+
+    #[test]
+    fn pcapng() {
+        fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+            let len = 12 + body.len().div_ceil(4) * 4;
+            let mut v = block_type.to_le_bytes().to_vec();
+            v.extend(&(len as u32).to_le_bytes());
+            v.extend(body);
+            v.resize(len - 4, 0);
+            v.extend(&(len as u32).to_le_bytes());
+            v
+        }
+
+        let record = &packets()[1];
+        let frame = ethernet_frame(&[0; 6], &[0xff; 6], record.src, record.dst, &record.payload);
+
+        let mut shb = PCAPNG_BYTE_ORDER.to_le_bytes().to_vec();
+        shb.extend(&[1, 0, 0, 0]); // version 1.0
+        shb.extend(&u64::MAX.to_le_bytes()); // unknown section length
+
+        // Interface with nanosecond timestamps
+        let mut idb = vec![1, 0, 0, 0, 0, 0, 0, 0];
+        idb.extend(&[PCAPNG_IF_TSRESOL as u8, 0, 1, 0, 9, 0, 0, 0]);
+        let ts: u64 = 1_100_000_001_000_000_500;
+        let mut epb = vec![0, 0, 0, 0];
+        epb.extend(&((ts >> 32) as u32).to_le_bytes());
+        epb.extend(&(ts as u32).to_le_bytes());
+        epb.extend(&(frame.len() as u32).to_le_bytes());
+        epb.extend(&(frame.len() as u32).to_le_bytes());
+        epb.extend(&frame);
+        // Non-DHCP frame, which is skipped
+        let mut other = epb[..12].to_vec();
+        other.extend(&[14, 0, 0, 0, 14, 0, 0, 0]);
+        other.extend(&[0; 12]);
+        other.extend(&[0x86, 0xdd]);
+
+        let mut capture = block(PCAPNG_SECTION_HEADER, &shb);
+        capture.extend(block(PCAPNG_INTERFACE_DESCRIPTION, &idb));
+        capture.extend(block(PCAPNG_ENHANCED_PACKET, &other));
+        capture.extend(block(PCAPNG_ENHANCED_PACKET, &epb));
+
+        let read: Vec<Record> = Reader::new(&capture[..])
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            read,
+            vec![Record {
+                timestamp: UNIX_EPOCH + Duration::new(1_100_000_001, 500),
+                ..record.clone()
+            }]
+        );
+    }
+
+    #[test]
+    fn truncated_frames() {
+        let mut ip = vec![0x45, 0, 0, 22, 0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0];
+        ip.extend(&[0, 0, 0, 0, 255, 255, 255, 255]);
+        ip.extend(&[0, 68, 0, 67, 0, 0, 0, 0]);
+        assert_eq!(dhcp_payload(LINKTYPE_RAW, &ip), None);
+
+        let mut capture = Writer::new(vec![]).unwrap().into_inner();
+        capture.extend(&[0; 8]);
+        capture.extend(&u32::MAX.to_le_bytes());
+        capture.extend(&u32::MAX.to_le_bytes());
+        let e = Reader::new(&capture[..])
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
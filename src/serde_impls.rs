@@ -0,0 +1,125 @@
+//! Serialize and Deserialize for types that cannot derive them.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::options::{decode_registered, title, Extension, RawDhcpOption};
+use crate::packet::HardwareAddress;
+
+/// HardwareAddress as its htype and colon separated hex address.
+#[derive(Serialize, Deserialize)]
+struct HardwareAddressRepr {
+    htype: u8,
+    addr: String,
+}
+
+impl Serialize for HardwareAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HardwareAddressRepr {
+            htype: self.htype(),
+            addr: self.to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HardwareAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = HardwareAddressRepr::deserialize(deserializer)?;
+        let addr = repr
+            .addr
+            .split(':')
+            .filter(|b| !b.is_empty())
+            .map(|b| u8::from_str_radix(b, 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(D::Error::custom)?;
+        HardwareAddress::new(repr.htype, &addr)
+            .ok_or_else(|| D::Error::custom("hardware address longer than 16 bytes"))
+    }
+}
+
+/// RawDhcpOption with the title of its code, which is ignored when deserializing.
+#[derive(Serialize, Deserialize)]
+struct RawDhcpOptionRepr {
+    code: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    data: Vec<u8>,
+}
+
+impl Serialize for RawDhcpOption {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawDhcpOptionRepr {
+            code: self.code,
+            name: title(self.code).map(str::to_string),
+            data: self.data.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawDhcpOption {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = RawDhcpOptionRepr::deserialize(deserializer)?;
+        Ok(RawDhcpOption {
+            code: repr.code,
+            data: repr.data,
+        })
+    }
+}
+
+/// Option codes as their titles, or numbers if unknown, for ParameterRequestList.
+pub(crate) mod option_codes {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Code {
+        Name(String),
+        Number(u8),
+    }
+
+    pub fn serialize<S: Serializer>(codes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(codes.iter().map(|&code| match title(code) {
+            Some(name) => Code::Name(name.to_string()),
+            None => Code::Number(code),
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Vec::<Code>::deserialize(deserializer)?
+            .into_iter()
+            .map(|code| match code {
+                Code::Number(code) => Ok(code),
+                Code::Name(name) => (0..=255)
+                    .find(|&code| title(code) == Some(name.as_str()))
+                    .ok_or_else(|| D::Error::custom(format!("unknown option {:?}", name))),
+            })
+            .collect()
+    }
+}
+
+/// Extension as its raw option, decoded again by the registered type.
+impl Serialize for Extension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawDhcpOption {
+            code: self.code(),
+            data: self.encode(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Extension {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawDhcpOption::deserialize(deserializer)?;
+        match decode_registered(raw.code, &raw.data) {
+            Some(Ok(ext)) => Ok(ext),
+            Some(Err(kind)) => Err(D::Error::custom(kind)),
+            None => Err(D::Error::custom(format!(
+                "no extension registered for option {}",
+                raw.code
+            ))),
+        }
+    }
+}
@@ -1,32 +1,68 @@
-use std::cell::Cell;
+//! This is a convenience module that simplifies the writing of a DHCP server service.
+
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 
 use crate::options;
 use crate::options::{DhcpOption, MessageType};
 use crate::packet::*;
 
-///! This is a convenience module that simplifies the writing of a DHCP server service.
-
 pub struct Server {
-    out_buf: Cell<[u8; 1500]>,
     socket: UdpSocket,
     src: SocketAddr,
+    max_message_size: usize,
     server_ip: Ipv4Addr,
 }
 
 pub trait Handler {
     fn handle_request(&mut self, server: &Server, in_packet: Packet);
+
+    /// Called for BOOTP requests (RFC 951), which have no DHCP_MESSAGE_TYPE.
+    /// Answer them with Server::reply_bootp. By default they are passed to handle_request.
+    fn handle_bootp_request(&mut self, server: &Server, in_packet: Packet) {
+        self.handle_request(server, in_packet)
+    }
+}
+
+/// Address permanently assigned to a BOOTP client. BOOTP clients cannot renew, so they hold
+/// the address with an infinite lease (RFC 1534).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticBinding {
+    pub ip: Ipv4Addr,
+    /// Server to boot from (siaddr), if the client boots over the network.
+    pub boot_server: Ipv4Addr,
+    /// Boot file name (file).
+    pub boot_file: Option<String>,
+    /// Options for clients that use RFC 1048 vendor extensions.
+    pub options: Vec<DhcpOption>,
 }
 
 /// Orders and filters options based on PARAMETER_REQUEST_LIST received from client.
 /// DHCP_MESSAGE_TYPE and SERVER_IDENTIFIER are always first and always retained.
+/// If MS_CLASSLESS_ROUTE_FORMAT is requested, it is copied from CLASSLESS_ROUTE_FORMAT.
+/// RELAY_AGENT_INFORMATION is always retained and moved last, as RFC 3046 requires.
 /// This function is called by Reply.
 pub fn filter_options_by_req(opts: &mut Vec<DhcpOption>, req_params: &[u8]) {
+    let relay_agent_info = opts
+        .iter()
+        .position(|o| o.code() == options::RELAY_AGENT_INFORMATION)
+        .map(|i| opts.remove(i));
+    if req_params.contains(&options::MS_CLASSLESS_ROUTE_FORMAT)
+        && !opts
+            .iter()
+            .any(|o| o.code() == options::MS_CLASSLESS_ROUTE_FORMAT)
+    {
+        if let Some(DhcpOption::ClasslessStaticRoute(routes)) = opts
+            .iter()
+            .find(|o| o.code() == options::CLASSLESS_ROUTE_FORMAT)
+        {
+            opts.push(DhcpOption::MsClasslessStaticRoute(routes.clone()));
+        }
+    }
     let mut pos = 0;
     let h = &[
-        options::DHCP_MESSAGE_TYPE as u8,
-        options::SERVER_IDENTIFIER as u8,
-        options::IP_ADDRESS_LEASE_TIME as u8,
+        options::DHCP_MESSAGE_TYPE,
+        options::SERVER_IDENTIFIER,
+        options::IP_ADDRESS_LEASE_TIME,
     ] as &[u8];
     for z in [h, req_params].iter() {
         for r in z.iter() {
@@ -46,6 +82,18 @@ pub fn filter_options_by_req(opts: &mut Vec<DhcpOption>, req_params: &[u8]) {
         }
     }
     opts.truncate(pos);
+    opts.extend(relay_agent_info);
+}
+
+/// Moves RELAY_AGENT_INFORMATION last, as RFC 3046 requires.
+fn relay_agent_info_last(opts: &mut Vec<DhcpOption>) {
+    if let Some(i) = opts
+        .iter()
+        .position(|o| o.code() == options::RELAY_AGENT_INFORMATION)
+    {
+        let info = opts.remove(i);
+        opts.push(info);
+    }
 }
 
 impl Server {
@@ -56,18 +104,24 @@ impl Server {
     ) -> std::io::Error {
         let mut in_buf: [u8; 1500] = [0; 1500];
         let mut s = Server {
-            out_buf: Cell::new([0; 1500]),
             socket: udp_soc,
             server_ip,
             src: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0),
+            max_message_size: MIN_MESSAGE_SIZE,
         };
         loop {
             match s.socket.recv_from(&mut in_buf) {
                 Err(e) => return e,
                 Ok((l, src)) => {
-                    if let Ok(p) = Packet::from(&in_buf[..l]) {
+                    // Leniently, to serve embedded clients that omit END or fill sname/file
+                    if let Ok((_, p)) = Packet::decode(&in_buf[..l], true) {
                         s.src = src;
-                        handler.handle_request(&s, p);
+                        s.max_message_size = p.max_message_size();
+                        if is_bootp_request(&p) {
+                            handler.handle_bootp_request(&s, p);
+                        } else {
+                            handler.handle_request(&s, p);
+                        }
                     }
                 }
             }
@@ -76,7 +130,9 @@ impl Server {
 
     /// Constructs and sends a reply packet back to the client.
     /// additional_options should not include DHCP_MESSAGE_TYPE nor SERVER_IDENTIFIER as these
-    /// are added automatically.
+    /// are added automatically. RELAY_AGENT_INFORMATION is echoed from req_packet, unless
+    /// additional_options supplies its own. Options are sent as given, without the RFC 2131
+    /// checks of PacketBuilder::build.
     pub fn reply(
         &self,
         msg_type: MessageType,
@@ -84,63 +140,308 @@ impl Server {
         offer_ip: Ipv4Addr,
         req_packet: Packet,
     ) -> std::io::Result<usize> {
-        let ciaddr = match msg_type {
-            MessageType::Nak => Ipv4Addr::new(0, 0, 0, 0),
-            _ => req_packet.ciaddr,
-        };
+        self.send(reply_packet(
+            self.server_ip,
+            msg_type,
+            additional_options,
+            offer_ip,
+            &req_packet,
+        ))
+    }
+
+    /// Constructs and sends a reply to a BOOTP client from its static binding.
+    /// If the request used RFC 1048 vendor extensions, the binding's options are sent, filtered
+    /// by any PARAMETER_REQUEST_LIST, with an infinite IP_ADDRESS_LEASE_TIME unless they have
+    /// their own. Otherwise the vendor area is left empty.
+    pub fn reply_bootp(
+        &self,
+        binding: &StaticBinding,
+        req_packet: Packet,
+    ) -> std::io::Result<usize> {
+        self.send(bootp_reply_packet(binding, &req_packet)?)
+    }
 
-        //let mt = &[msg_type as u8];
+    /// Checks the packet see if it was intended for this DHCP server (as opposed to some other also on the network).
+    pub fn for_this_server(&self, packet: &Packet) -> bool {
+        for_server(packet, self.server_ip)
+    }
 
-        let mut opts: Vec<DhcpOption> = Vec::with_capacity(additional_options.len() + 2);
-        opts.push(DhcpOption::DhcpMessageType(msg_type));
-        opts.push(DhcpOption::ServerIdentifier(self.server_ip));
-        /*opts.push(DhcpOption {
-            code: options::DHCP_MESSAGE_TYPE,
-            data: mt,
-        });
-        opts.push(DhcpOption {
-            code: options::SERVER_IDENTIFIER,
-            data: &self.server_ip,
-        });*/
-        opts.extend(additional_options);
+    /// Encodes and sends a DHCP packet back to the client.
+    /// Options are overloaded into sname and file if they would exceed the client's
+    /// maximum message size.
+    pub fn send(&self, p: Packet) -> std::io::Result<usize> {
+        let (out, addr) = encode_reply(&p, self.src, self.max_message_size)?;
+        self.socket.send_to(&out, addr)
+    }
+}
+
+/// True for BOOTP requests (RFC 951), which have no DHCP_MESSAGE_TYPE.
+pub(crate) fn is_bootp_request(p: &Packet) -> bool {
+    !p.reply && p.option(options::DHCP_MESSAGE_TYPE).is_none()
+}
+
+pub(crate) fn for_server(packet: &Packet, server_ip: Ipv4Addr) -> bool {
+    match packet.option(options::SERVER_IDENTIFIER) {
+        Some(DhcpOption::ServerIdentifier(x)) => x == &server_ip,
+        _ => false,
+    }
+}
+
+/// Builds the reply for Server::reply.
+pub(crate) fn reply_packet(
+    server_ip: Ipv4Addr,
+    msg_type: MessageType,
+    additional_options: Vec<DhcpOption>,
+    offer_ip: Ipv4Addr,
+    req_packet: &Packet,
+) -> Packet {
+    let mut opts: Vec<DhcpOption> = Vec::with_capacity(additional_options.len() + 2);
+    opts.push(DhcpOption::DhcpMessageType(msg_type));
+    opts.push(DhcpOption::ServerIdentifier(server_ip));
+    opts.extend(additional_options);
+    if let Some(info) = req_packet.option(options::RELAY_AGENT_INFORMATION) {
+        if !opts
+            .iter()
+            .any(|o| o.code() == options::RELAY_AGENT_INFORMATION)
+        {
+            opts.push(info.clone());
+        }
+    }
+
+    match req_packet.option(options::PARAMETER_REQUEST_LIST) {
+        Some(DhcpOption::ParameterRequestList(prl)) => filter_options_by_req(&mut opts, prl),
+        _ => relay_agent_info_last(&mut opts),
+    }
+
+    PacketBuilder::reply_to(req_packet)
+        .yiaddr(offer_ip)
+        .options(opts)
+        .build_unchecked()
+}
 
+/// Builds the reply for Server::reply_bootp.
+pub(crate) fn bootp_reply_packet(
+    binding: &StaticBinding,
+    req_packet: &Packet,
+) -> std::io::Result<Packet> {
+    let mut b = PacketBuilder::reply_to(req_packet)
+        .yiaddr(binding.ip)
+        .siaddr(binding.boot_server);
+    if let Some(file) = &binding.boot_file {
+        b = b.file(file);
+    }
+    if req_packet.vend.is_some() {
+        b = b.vend(&[]);
+    } else {
+        let mut opts = binding.options.clone();
+        if !opts
+            .iter()
+            .any(|o| o.code() == options::IP_ADDRESS_LEASE_TIME)
+        {
+            opts.push(DhcpOption::IpAddressLeaseTime(options::INFINITE_LEASE_TIME));
+        }
         if let Some(DhcpOption::ParameterRequestList(prl)) =
             req_packet.option(options::PARAMETER_REQUEST_LIST)
         {
-            filter_options_by_req(&mut opts, &prl);
+            filter_options_by_req(&mut opts, prl);
         }
+        b = b.options(opts);
+    }
+    b.build_bootp()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
 
-        self.send(Packet {
-            reply: true,
-            hops: 0,
-            xid: req_packet.xid,
-            secs: 0,
-            broadcast: req_packet.broadcast,
-            ciaddr,
-            yiaddr: offer_ip,
-            siaddr: Ipv4Addr::new(0, 0, 0, 0),
-            giaddr: req_packet.giaddr,
-            chaddr: req_packet.chaddr,
-            options: opts,
-        })
+/// Encodes a reply to the client at src, returning it with the address to send it to.
+pub(crate) fn encode_reply(
+    p: &Packet,
+    src: SocketAddr,
+    max_message_size: usize,
+) -> std::io::Result<(Vec<u8>, SocketAddr)> {
+    let mut addr = src;
+    if p.flags.broadcast() || addr.ip() == IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)) {
+        addr.set_ip(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 255)));
     }
+    let out = p
+        .encode_to_vec(max_message_size)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    Ok((out, addr))
+}
 
-    /// Checks the packet see if it was intended for this DHCP server (as opposed to some other also on the network).
-    pub fn for_this_server(&self, packet: &Packet) -> bool {
-        match packet.option(options::SERVER_IDENTIFIER) {
-            Some(DhcpOption::ServerIdentifier(x)) => {
-                x == &self.server_ip
-            },
-            _ => false,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply_bootp() {
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = Server {
+            socket: UdpSocket::bind("127.0.0.1:0").unwrap(),
+            src: client.local_addr().unwrap(),
+            max_message_size: BOOTP_MESSAGE_SIZE,
+            server_ip: Ipv4Addr::new(192, 168, 0, 1),
+        };
+        let binding = StaticBinding {
+            ip: Ipv4Addr::new(192, 168, 0, 20),
+            boot_server: Ipv4Addr::new(192, 168, 0, 2),
+            boot_file: Some("printer.bin".to_string()),
+            options: vec![DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0))],
+        };
+        let chaddr = HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]);
+        let mut buf = [0; 1500];
+
+        let request = PacketBuilder::request(chaddr)
+            .vend(&[0; 64])
+            .build_bootp()
+            .unwrap();
+        let len = server.reply_bootp(&binding, request.clone()).unwrap();
+        assert_eq!(len, BOOTP_MESSAGE_SIZE);
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        let reply = Packet::from(&buf[..len]).unwrap();
+        assert!(reply.reply);
+        assert_eq!(reply.xid, request.xid);
+        assert_eq!(reply.yiaddr, binding.ip);
+        assert_eq!(reply.siaddr, binding.boot_server);
+        assert_eq!(reply.file, binding.boot_file);
+        assert_eq!(reply.vend, Some(vec![0; 64]));
+
+        let request = PacketBuilder::request(chaddr).build_bootp().unwrap();
+        server.reply_bootp(&binding, request).unwrap();
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(
+            Packet::from(&buf[..len]).unwrap().options,
+            vec![
+                DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+                DhcpOption::IpAddressLeaseTime(options::INFINITE_LEASE_TIME),
+            ]
+        );
     }
 
-    /// Encodes and sends a DHCP packet back to the client.
-    pub fn send(&self, p: Packet) -> std::io::Result<usize> {
-        let mut addr = self.src;
-        if p.broadcast || addr.ip() == IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)) {
-            addr.set_ip(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 255)));
+    #[test]
+    fn filter_adds_ms_classless_route() {
+        let routes = vec![("10.0.0.0/8".parse().unwrap(), Ipv4Addr::new(192, 168, 0, 1))];
+        let mut opts = vec![
+            DhcpOption::DhcpMessageType(options::MessageType::Offer),
+            DhcpOption::ClasslessStaticRoute(routes.clone()),
+        ];
+        filter_options_by_req(
+            &mut opts,
+            &[options::SUBNET_MASK, options::MS_CLASSLESS_ROUTE_FORMAT],
+        );
+        assert_eq!(
+            opts,
+            vec![
+                DhcpOption::DhcpMessageType(options::MessageType::Offer),
+                DhcpOption::MsClasslessStaticRoute(routes),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_keeps_relay_agent_information_last() {
+        let info =
+            DhcpOption::RelayAgentInformation(vec![options::RelayAgentSubOption::CircuitId(
+                b"eth0".to_vec(),
+            )]);
+        let mut opts = vec![
+            DhcpOption::DhcpMessageType(options::MessageType::Ack),
+            info.clone(),
+            DhcpOption::Router(vec![Ipv4Addr::new(192, 168, 0, 1)]),
+            DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+        ];
+        filter_options_by_req(&mut opts, &[options::SUBNET_MASK, options::ROUTER]);
+        assert_eq!(
+            opts,
+            vec![
+                DhcpOption::DhcpMessageType(options::MessageType::Ack),
+                DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)),
+                DhcpOption::Router(vec![Ipv4Addr::new(192, 168, 0, 1)]),
+                info,
+            ]
+        );
+    }
+
+    #[test]
+    fn reply_keeps_relay_agent_information_last_without_prl() {
+        let info =
+            DhcpOption::RelayAgentInformation(vec![options::RelayAgentSubOption::CircuitId(
+                b"eth0".to_vec(),
+            )]);
+        let request = PacketBuilder::request(HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]))
+            .message_type(MessageType::Request)
+            .option(info.clone())
+            .build()
+            .unwrap();
+        let reply = reply_packet(
+            Ipv4Addr::new(192, 168, 0, 1),
+            MessageType::Nak,
+            vec![info.clone(), DhcpOption::Message("no".to_string())],
+            Ipv4Addr::UNSPECIFIED,
+            &request,
+        );
+        assert_eq!(reply.options.last(), Some(&info));
+    }
+
+    #[test]
+    fn reply_offer_without_lease_time() {
+        let request = PacketBuilder::request(HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]))
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+        let offer = reply_packet(
+            Ipv4Addr::new(192, 168, 0, 1),
+            MessageType::Offer,
+            vec![],
+            Ipv4Addr::new(192, 168, 0, 10),
+            &request,
+        );
+        assert!(offer.reply);
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 0, 10));
+        assert_eq!(offer.message_type(), Ok(MessageType::Offer));
+    }
+
+    struct Offerer;
+
+    impl Handler for Offerer {
+        fn handle_request(&mut self, server: &Server, in_packet: Packet) {
+            server
+                .reply(
+                    MessageType::Offer,
+                    vec![DhcpOption::IpAddressLeaseTime(3600)],
+                    Ipv4Addr::new(192, 168, 0, 10),
+                    in_packet,
+                )
+                .unwrap();
         }
-        self.socket.send_to(p.encode(&mut self.out_buf.get()), addr)
+    }
+
+    #[test]
+    fn serve_lenient() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        std::thread::spawn(move || {
+            Server::serve(socket, Ipv4Addr::new(192, 168, 0, 1), Offerer);
+        });
+
+        let discover = PacketBuilder::request(HardwareAddress::ethernet([0, 1, 2, 3, 4, 5]))
+            .message_type(MessageType::Discover)
+            .build()
+            .unwrap();
+        let mut out = discover.encode_to_vec(MIN_MESSAGE_SIZE).unwrap();
+        // Non-UTF-8 sname, and END replaced by PAD
+        out[44] = 0xff;
+        assert_eq!(out[243], 255);
+        out[243] = 0;
+        assert!(Packet::from(&out).is_err());
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        client.send_to(&out, addr).unwrap();
+        let mut buf = [0; 1500];
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        let offer = Packet::from(&buf[..len]).unwrap();
+        assert_eq!(offer.xid, discover.xid);
+        assert_eq!(offer.message_type(), Ok(MessageType::Offer));
     }
 }
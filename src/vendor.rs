@@ -0,0 +1,381 @@
+//! Interpretation of VENDOR_SPECIFIC_INFORMATION, whose contents depend on the client's
+//! VENDOR_CLASS_IDENTIFIER.
+
+use std::net::Ipv4Addr;
+
+use crate::options::*;
+use crate::packet::{decode_encapsulated, DecodeError, DecodeErrorKind, EncodeError, Packet};
+
+/// Decoded VENDOR_SPECIFIC_INFORMATION.
+#[derive(PartialEq, Clone, Debug)]
+pub enum VendorInfo {
+    /// Encapsulated sub-options.
+    SubOptions(Vec<VendorSubOption>),
+    /// Data that is a single string rather than sub-options, as sent to Aruba APs.
+    Text(String),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct VendorSubOption {
+    pub code: u8,
+    pub value: VendorValue,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum VendorValue {
+    Address(Ipv4Addr),
+    Addresses(Vec<Ipv4Addr>),
+    U8(u8),
+    U16(u16),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+/// Type of a sub-option's value, used by Encapsulated to decode it.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ValueKind {
+    Address,
+    Addresses,
+    U8,
+    U16,
+    String,
+    Bytes,
+}
+
+/// Decodes and encodes one vendor's VENDOR_SPECIFIC_INFORMATION.
+pub trait VendorDecoder: Send + Sync {
+    /// Returns title of sub-option code, if known.
+    fn title(&self, code: u8) -> Option<&'static str>;
+    /// Decodes option data, with error offsets relative to data.
+    fn decode(&self, data: &[u8]) -> Result<VendorInfo, DecodeError>;
+    fn encode(&self, info: &VendorInfo) -> Result<DhcpOption, EncodeError>;
+}
+
+/// Decoder for encapsulated sub-options, described by (code, title, kind).
+/// Sub-options not listed are kept as VendorValue::Bytes.
+pub struct Encapsulated {
+    pub sub_options: &'static [(u8, &'static str, ValueKind)],
+    /// Whether encoded sub-options are terminated by END, as PXE requires.
+    pub terminated: bool,
+}
+
+impl Encapsulated {
+    fn kind(&self, code: u8) -> ValueKind {
+        match self.sub_options.iter().find(|(c, _, _)| *c == code) {
+            Some((_, _, kind)) => *kind,
+            None => ValueKind::Bytes,
+        }
+    }
+}
+
+fn decode_value(kind: ValueKind, data: &[u8]) -> Result<VendorValue, DecodeErrorKind> {
+    let addrs = || {
+        if data.len() % 4 != 0 {
+            return Err(DecodeErrorKind::InvalidLength(data.len()));
+        }
+        Ok(data
+            .chunks(4)
+            .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+            .collect::<Vec<_>>())
+    };
+    Ok(match (kind, data) {
+        (ValueKind::Address, [a, b, c, d]) => VendorValue::Address(Ipv4Addr::new(*a, *b, *c, *d)),
+        (ValueKind::Addresses, _) => VendorValue::Addresses(addrs()?),
+        (ValueKind::U8, [v]) => VendorValue::U8(*v),
+        (ValueKind::U16, [a, b]) => VendorValue::U16(u16::from_be_bytes([*a, *b])),
+        (ValueKind::String, _) => match std::str::from_utf8(data) {
+            Ok(s) => VendorValue::String(s.to_string()),
+            Err(_) => return Err(DecodeErrorKind::NonUtf8String),
+        },
+        (ValueKind::Bytes, _) => VendorValue::Bytes(data.to_vec()),
+        _ => return Err(DecodeErrorKind::InvalidLength(data.len())),
+    })
+}
+
+fn encode_value(value: &VendorValue) -> Vec<u8> {
+    match value {
+        VendorValue::Address(addr) => addr.octets().to_vec(),
+        VendorValue::Addresses(addrs) => addrs.iter().flat_map(|a| a.octets()).collect(),
+        VendorValue::U8(v) => vec![*v],
+        VendorValue::U16(v) => v.to_be_bytes().to_vec(),
+        VendorValue::String(s) => s.as_bytes().to_vec(),
+        VendorValue::Bytes(b) => b.clone(),
+    }
+}
+
+impl VendorDecoder for Encapsulated {
+    fn title(&self, code: u8) -> Option<&'static str> {
+        self.sub_options
+            .iter()
+            .find(|(c, _, _)| *c == code)
+            .map(|(_, title, _)| *title)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<VendorInfo, DecodeError> {
+        let mut subs = vec![];
+        let mut at = 0;
+        for raw in decode_encapsulated(data)? {
+            // Skip PAD and the sub-option's code and length to locate its data.
+            while data[at] == 0 {
+                at += 1;
+            }
+            at += 2;
+            match decode_value(self.kind(raw.code), &raw.data) {
+                Ok(value) => subs.push(VendorSubOption {
+                    code: raw.code,
+                    value,
+                }),
+                Err(kind) => {
+                    return Err(DecodeError {
+                        offset: at,
+                        option: Some(VENDOR_SPECIFIC_INFORMATION),
+                        kind,
+                    })
+                }
+            }
+            at += raw.data.len();
+        }
+        Ok(VendorInfo::SubOptions(subs))
+    }
+
+    fn encode(&self, info: &VendorInfo) -> Result<DhcpOption, EncodeError> {
+        match info {
+            VendorInfo::SubOptions(subs) => sub_options_option(subs, self.terminated),
+            VendorInfo::Text(s) => Ok(text_option(s)),
+        }
+    }
+}
+
+/// Decoder for data that is a single string.
+pub struct Text;
+
+impl VendorDecoder for Text {
+    fn title(&self, _code: u8) -> Option<&'static str> {
+        None
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<VendorInfo, DecodeError> {
+        match std::str::from_utf8(data) {
+            Ok(s) => Ok(VendorInfo::Text(s.to_string())),
+            Err(e) => Err(DecodeError {
+                offset: e.valid_up_to(),
+                option: Some(VENDOR_SPECIFIC_INFORMATION),
+                kind: DecodeErrorKind::NonUtf8String,
+            }),
+        }
+    }
+
+    fn encode(&self, info: &VendorInfo) -> Result<DhcpOption, EncodeError> {
+        match info {
+            VendorInfo::Text(s) => Ok(text_option(s)),
+            VendorInfo::SubOptions(subs) => sub_options_option(subs, false),
+        }
+    }
+}
+
+/// Encodes sub-options in RFC 2132 encapsulated format, failing if one is longer than 255
+/// bytes.
+fn sub_options_option(
+    subs: &[VendorSubOption],
+    terminated: bool,
+) -> Result<DhcpOption, EncodeError> {
+    let mut v = vec![];
+    for sub in subs {
+        let data = encode_value(&sub.value);
+        if data.len() > 255 {
+            return Err(EncodeError::ItemTooLong {
+                option: VENDOR_SPECIFIC_INFORMATION,
+                len: data.len(),
+            });
+        }
+        v.push(sub.code);
+        v.push(data.len() as u8);
+        v.extend(data);
+    }
+    if terminated && !v.is_empty() {
+        v.push(255);
+    }
+    Ok(DhcpOption::VendorSpecificInformation(v))
+}
+
+fn text_option(s: &str) -> DhcpOption {
+    DhcpOption::VendorSpecificInformation(s.as_bytes().to_vec())
+}
+
+/// PXE client sub-options (PXE specification 2.1).
+pub const PXE_CLIENT: Encapsulated = Encapsulated {
+    sub_options: &[
+        (1, "PXE MTFTP IP", ValueKind::Address),
+        (2, "PXE MTFTP Client Port", ValueKind::U16),
+        (3, "PXE MTFTP Server Port", ValueKind::U16),
+        (4, "PXE MTFTP Timeout", ValueKind::U8),
+        (5, "PXE MTFTP Delay", ValueKind::U8),
+        (6, "PXE Discovery Control", ValueKind::U8),
+        (7, "PXE Discovery Multicast Address", ValueKind::Address),
+        (8, "PXE Boot Servers", ValueKind::Bytes),
+        (9, "PXE Boot Menu", ValueKind::Bytes),
+        (10, "PXE Menu Prompt", ValueKind::Bytes),
+        (71, "PXE Boot Item", ValueKind::Bytes),
+    ],
+    terminated: true,
+};
+
+/// Cisco lightweight access point sub-options.
+pub const CISCO_AP: Encapsulated = Encapsulated {
+    sub_options: &[(
+        241,
+        "Wireless LAN Controller Addresses",
+        ValueKind::Addresses,
+    )],
+    terminated: false,
+};
+
+/// Decoders keyed on VENDOR_CLASS_IDENTIFIER prefix.
+pub struct VendorRegistry {
+    decoders: Vec<(Vec<u8>, Box<dyn VendorDecoder>)>,
+}
+
+impl Default for VendorRegistry {
+    /// Registry with PXEClient, Cisco AP and ArubaAP decoders.
+    fn default() -> Self {
+        let mut registry = VendorRegistry::new();
+        registry.register(b"PXEClient", Box::new(PXE_CLIENT));
+        registry.register(b"Cisco AP", Box::new(CISCO_AP));
+        registry.register(b"ArubaAP", Box::new(Text));
+        registry
+    }
+}
+
+impl VendorRegistry {
+    /// Empty registry.
+    pub fn new() -> Self {
+        VendorRegistry { decoders: vec![] }
+    }
+
+    /// Registers decoder for vendor classes starting with class_prefix, replacing any
+    /// decoder already registered for the same prefix.
+    pub fn register(&mut self, class_prefix: &[u8], decoder: Box<dyn VendorDecoder>) {
+        self.decoders.retain(|(p, _)| p != class_prefix);
+        self.decoders.push((class_prefix.to_vec(), decoder));
+    }
+
+    /// Returns the decoder with the longest prefix of vendor_class.
+    pub fn lookup(&self, vendor_class: &[u8]) -> Option<&dyn VendorDecoder> {
+        self.decoders
+            .iter()
+            .filter(|(p, _)| vendor_class.starts_with(p))
+            .max_by_key(|(p, _)| p.len())
+            .map(|(_, d)| d.as_ref())
+    }
+
+    /// Decodes the packet's VENDOR_SPECIFIC_INFORMATION according to its
+    /// VENDOR_CLASS_IDENTIFIER. Returns None if either is missing or the class is unknown.
+    pub fn decode(&self, packet: &Packet) -> Option<Result<VendorInfo, DecodeError>> {
+        let decoder = self.lookup(vendor_class(packet)?)?;
+        match packet.option(VENDOR_SPECIFIC_INFORMATION)? {
+            DhcpOption::VendorSpecificInformation(data) => Some(decoder.decode(data)),
+            _ => None,
+        }
+    }
+
+    /// Encodes info as VENDOR_SPECIFIC_INFORMATION for a reply to request, according to the
+    /// request's VENDOR_CLASS_IDENTIFIER.
+    pub fn encode_reply(
+        &self,
+        request: &Packet,
+        info: &VendorInfo,
+    ) -> Option<Result<DhcpOption, EncodeError>> {
+        Some(self.lookup(vendor_class(request)?)?.encode(info))
+    }
+}
+
+fn vendor_class(packet: &Packet) -> Option<&[u8]> {
+    match packet.option(VENDOR_CLASS_IDENTIFIER) {
+        Some(DhcpOption::VendorClassIdentifier(class)) => Some(class),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PacketBuilder;
+
+    fn request(class: &[u8], vendor: DhcpOption) -> Packet {
+        PacketBuilder::request([0, 0x1b, 0x21, 0x3c, 0x4d, 0x5e].into())
+            .message_type(MessageType::Discover)
+            .option(DhcpOption::VendorClassIdentifier(class.to_vec()))
+            .option(vendor)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn pxe_client() {
+        let registry = VendorRegistry::default();
+        let option =
+            DhcpOption::VendorSpecificInformation(vec![6, 1, 8, 10, 4, 0, b'P', b'X', b'E', 255]);
+        let p = request(b"PXEClient:Arch:00000:UNDI:002001", option.clone());
+        let info = VendorInfo::SubOptions(vec![
+            VendorSubOption {
+                code: 6,
+                value: VendorValue::U8(8),
+            },
+            VendorSubOption {
+                code: 10,
+                value: VendorValue::Bytes(vec![0, b'P', b'X', b'E']),
+            },
+        ]);
+        assert_eq!(registry.decode(&p), Some(Ok(info.clone())));
+        assert_eq!(registry.encode_reply(&p, &info), Some(Ok(option)));
+        assert_eq!(
+            registry.lookup(b"PXEClient").unwrap().title(6),
+            Some("PXE Discovery Control")
+        );
+
+        let bad = DhcpOption::VendorSpecificInformation(vec![6, 2, 8, 0, 255]);
+        let e = registry
+            .decode(&request(b"PXEClient", bad))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!((e.offset, e.kind), (2, DecodeErrorKind::InvalidLength(2)));
+
+        let long = VendorInfo::SubOptions(vec![VendorSubOption {
+            code: 71,
+            value: VendorValue::Bytes(vec![0; 256]),
+        }]);
+        assert_eq!(
+            registry.encode_reply(&p, &long),
+            Some(Err(EncodeError::ItemTooLong {
+                option: VENDOR_SPECIFIC_INFORMATION,
+                len: 256
+            }))
+        );
+    }
+
+    #[test]
+    fn cisco_and_aruba() {
+        let registry = VendorRegistry::default();
+        let wlcs = DhcpOption::VendorSpecificInformation(vec![241, 8, 10, 0, 0, 1, 10, 0, 0, 2]);
+        let p = request(b"Cisco AP c3700", wlcs.clone());
+        let info = VendorInfo::SubOptions(vec![VendorSubOption {
+            code: 241,
+            value: VendorValue::Addresses(vec![
+                Ipv4Addr::new(10, 0, 0, 1),
+                Ipv4Addr::new(10, 0, 0, 2),
+            ]),
+        }]);
+        assert_eq!(registry.decode(&p), Some(Ok(info.clone())));
+        // Cisco sub-options are not terminated by END
+        assert_eq!(registry.encode_reply(&p, &info), Some(Ok(wlcs)));
+
+        let controller = text_option("192.168.0.10");
+        let p = request(b"ArubaAP", controller.clone());
+        let info = VendorInfo::Text("192.168.0.10".to_string());
+        assert_eq!(registry.decode(&p), Some(Ok(info.clone())));
+        assert_eq!(registry.encode_reply(&p, &info), Some(Ok(controller)));
+
+        let p = request(b"MSFT 5.0", text_option("x"));
+        assert_eq!(registry.decode(&p), None);
+    }
+}